@@ -1,7 +1,11 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
+}
+
+fn generate(_rng: &mut Rng, _size: usize) -> String {
+    String::new()
 }
 
 fn part1(_input: &str) -> Result<i32> {
@@ -0,0 +1,421 @@
+use aoc2018::Result;
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
+use failure::{bail, err_msg, format_err};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+fn main() {
+    if let Err(err) = run() {
+        println!("{}", err);
+    }
+}
+
+fn run() -> Result<()> {
+    let matches = App::new("adventofcode")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Answers JSON-lines requests, keeping a warm process per day")
+                .arg(
+                    Arg::with_name("stdio")
+                        .long("stdio")
+                        .help("Serves requests on stdin/stdout"),
+                )
+                .arg(
+                    Arg::with_name("tcp")
+                        .long("tcp")
+                        .takes_value(true)
+                        .value_name("addr")
+                        .help("Serves requests on a TCP address, e.g. 127.0.0.1:7878"),
+                )
+                .arg(
+                    Arg::with_name("unix")
+                        .long("unix")
+                        .takes_value(true)
+                        .value_name("path")
+                        .help("Serves requests on a Unix socket"),
+                )
+                .arg(
+                    Arg::with_name("timeout")
+                        .long("timeout")
+                        .takes_value(true)
+                        .value_name("secs")
+                        .default_value("60")
+                        .help("Restarts a day's worker that takes longer than this to answer"),
+                )
+                .group(
+                    ArgGroup::with_name("transport")
+                        .args(&["stdio", "tcp", "unix"])
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("gen")
+                .about("Prints a random puzzle input for a day")
+                .arg(Arg::with_name("day").required(true).index(1))
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .takes_value(true)
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::with_name("size")
+                        .long("size")
+                        .takes_value(true)
+                        .default_value("10"),
+                ),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        ("serve", Some(matches)) => serve(matches),
+        ("gen", Some(matches)) => generate(matches),
+        _ => unreachable!(),
+    }
+}
+
+fn parse_day(day: &str) -> Result<u64> {
+    match day.parse() {
+        Ok(day) if (1..=25).contains(&day) => Ok(day),
+        _ => bail!("`day` must be between 1 and 25"),
+    }
+}
+
+fn day_binary(day: u64) -> Result<PathBuf> {
+    let exe = env::current_exe()?;
+    let dir = exe
+        .parent()
+        .ok_or_else(|| err_msg("can't locate the day binaries"))?;
+    Ok(dir.join(format!("day{:02}{}", day, env::consts::EXE_SUFFIX)))
+}
+
+fn day_command(day: u64) -> Result<Command> {
+    let mut command = Command::new(day_binary(day)?);
+    command.arg("--serve");
+    Ok(command)
+}
+
+/// A `dayNN --serve` process kept alive between requests.
+struct Worker {
+    child: Child,
+    stdin: ChildStdin,
+    responses: Receiver<Value>,
+}
+
+impl Worker {
+    fn spawn(mut command: Command) -> Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| err_msg("no worker stdin"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| err_msg("no worker stdout"))?;
+        // read on a thread of its own so a request can give up on a hung worker
+        let (sender, responses) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                match serde_json::from_str(&line) {
+                    Ok(response @ Value::Object(_)) => {
+                        if sender.send(response).is_err() {
+                            break;
+                        }
+                    }
+                    // some solvers print progress to stdout; keep it out of the responses
+                    _ => eprintln!("{}", line),
+                }
+            }
+        });
+        Ok(Worker {
+            child,
+            stdin,
+            responses,
+        })
+    }
+
+    fn request(&mut self, request: &Value, timeout: Duration) -> Result<Value> {
+        writeln!(self.stdin, "{}", request)?;
+        self.stdin.flush()?;
+        match self.responses.recv_timeout(timeout) {
+            Ok(response) => Ok(response),
+            Err(RecvTimeoutError::Timeout) => {
+                bail!("no answer within {}s", timeout.as_secs_f64())
+            }
+            Err(RecvTimeoutError::Disconnected) => bail!("worker exited"),
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn error_response(request: &Value, message: &str) -> Value {
+    let mut response = json!({ "error": message });
+    for key in &["id", "day", "part"] {
+        if let Some(value) = request.get(key) {
+            response[key] = value.clone();
+        }
+    }
+    response
+}
+
+/// A day's worker, or `None` until the next request (re)starts it.
+type Slot = Arc<Mutex<Option<Worker>>>;
+
+/// The warm workers, each behind its own lock so a slow day only holds up
+/// requests for that same day.
+struct Pool {
+    workers: Mutex<HashMap<u64, Slot>>,
+    command: fn(u64) -> Result<Command>,
+    timeout: Duration,
+}
+
+impl Pool {
+    fn new(timeout: Duration) -> Self {
+        Pool {
+            workers: Mutex::new(HashMap::new()),
+            command: day_command,
+            timeout,
+        }
+    }
+
+    fn slot(&self, day: u64) -> Result<Slot> {
+        let mut workers = self
+            .workers
+            .lock()
+            .map_err(|_| err_msg("worker pool poisoned"))?;
+        Ok(Arc::clone(workers.entry(day).or_default()))
+    }
+
+    fn request(&self, day: u64, request: &Value) -> Result<Value> {
+        let slot = self.slot(day)?;
+        let mut worker = slot
+            .lock()
+            .map_err(|_| format_err!("day {} worker poisoned", day))?;
+        if worker.is_none() {
+            *worker = Some(Worker::spawn((self.command)(day)?)?);
+        }
+        let response = worker
+            .as_mut()
+            .map(|worker| worker.request(request, self.timeout))
+            .unwrap_or_else(|| Err(err_msg("no worker")));
+        if response.is_err() {
+            // kill it, hung or not, and start afresh on the next request
+            *worker = None;
+        }
+        response
+    }
+
+    fn handle(&self, line: &str) -> Value {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(err) => return json!({ "error": format!("invalid request: {}", err) }),
+        };
+        let day = match request["day"].as_u64() {
+            Some(day) if (1..=25).contains(&day) => day,
+            _ => return error_response(&request, "`day` must be between 1 and 25"),
+        };
+        match self.request(day, &request) {
+            Ok(response) => response,
+            Err(err) => error_response(&request, &format!("day {}: {}", day, err)),
+        }
+    }
+}
+
+fn serve_stream(pool: &Pool, reader: impl BufRead, mut writer: impl Write) -> Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = pool.handle(&line);
+        writeln!(writer, "{}", response)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// A socket connection that can be split into a reading and a writing half.
+trait Connection: Read + Write + Send + Sized + 'static {
+    fn try_clone(&self) -> io::Result<Self>;
+}
+
+impl Connection for TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        UnixStream::try_clone(self)
+    }
+}
+
+/// Serves each connection on a thread of its own.
+fn serve_connections<C: Connection>(
+    pool: Arc<Pool>,
+    incoming: impl Iterator<Item = io::Result<C>>,
+) -> Result<()> {
+    for stream in incoming {
+        let stream = stream?;
+        let pool = Arc::clone(&pool);
+        thread::spawn(move || {
+            let res = stream
+                .try_clone()
+                .map_err(|err| err.into())
+                .and_then(|reader| serve_stream(&pool, BufReader::new(reader), stream));
+            if let Err(err) = res {
+                eprintln!("connection: {}", err);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn serve_tcp(pool: Arc<Pool>, addr: &str) -> Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    serve_connections(pool, listener.incoming())
+}
+
+#[cfg(unix)]
+fn serve_unix(pool: Arc<Pool>, path: &str) -> Result<()> {
+    let listener = UnixListener::bind(path)?;
+    serve_connections(pool, listener.incoming())
+}
+
+#[cfg(not(unix))]
+fn serve_unix(_pool: Arc<Pool>, _path: &str) -> Result<()> {
+    bail!("unix sockets are not supported on this platform")
+}
+
+fn serve(matches: &ArgMatches) -> Result<()> {
+    let timeout: f64 = matches.value_of("timeout").unwrap_or("60").parse()?;
+    if !timeout.is_finite() || timeout <= 0.0 {
+        bail!("`timeout` must be a positive number of seconds");
+    }
+    let pool = Arc::new(Pool::new(Duration::from_secs_f64(timeout)));
+    if let Some(addr) = matches.value_of("tcp") {
+        serve_tcp(pool, addr)
+    } else if let Some(path) = matches.value_of("unix") {
+        serve_unix(pool, path)
+    } else {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        serve_stream(&pool, stdin.lock(), stdout.lock())
+    }
+}
+
+fn generate(matches: &ArgMatches) -> Result<()> {
+    let day = parse_day(matches.value_of("day").unwrap_or(""))?;
+    let seed: u64 = matches.value_of("seed").unwrap_or("0").parse()?;
+    let size: usize = matches.value_of("size").unwrap_or("10").parse()?;
+    let status = Command::new(day_binary(day)?)
+        .arg("--gen")
+        .arg("--seed")
+        .arg(seed.to_string())
+        .arg("--size")
+        .arg(size.to_string())
+        .status()?;
+    if !status.success() {
+        bail!("day {} generator failed", day);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_handle_bad_day() {
+        let pool = Pool::new(Duration::from_secs(1));
+        let response = pool.handle(r#"{"id": "a", "day": 26, "part": 1, "input": ""}"#);
+        assert_eq!(response["error"], "`day` must be between 1 and 25");
+        assert_eq!(response["id"], "a");
+    }
+
+    /// Day 1 never answers; every other day echoes a fixed answer.
+    #[cfg(unix)]
+    fn fake_day(day: u64) -> Result<Command> {
+        let mut command = Command::new("sh");
+        if day == 1 {
+            command.args(["-c", "sleep 30"]);
+        } else {
+            command.args(["-c", r#"while read line; do echo '{"answer":"ok"}'; done"#]);
+        }
+        Ok(command)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_serve_connections() -> Result<()> {
+        let pool = Arc::new(Pool {
+            command: fake_day,
+            ..Pool::new(Duration::from_secs(1))
+        });
+        let (mut client, server) = UnixStream::pair()?;
+        serve_connections(pool, vec![Ok(server)].into_iter())?;
+        writeln!(client, r#"{{"id": 1, "day": 2, "part": 1, "input": ""}}"#)?;
+        let mut line = String::new();
+        BufReader::new(client).read_line(&mut line)?;
+        assert_eq!(line, "{\"answer\":\"ok\"}\n");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hung_worker() {
+        let pool = Arc::new(Pool {
+            command: fake_day,
+            ..Pool::new(Duration::from_millis(500))
+        });
+        let hung = {
+            let pool = Arc::clone(&pool);
+            thread::spawn(move || pool.handle(r#"{"id": 1, "day": 1, "part": 1, "input": ""}"#))
+        };
+        thread::sleep(Duration::from_millis(100));
+
+        // another day answers while day 1 is still stuck
+        let start = Instant::now();
+        let response = pool.handle(r#"{"id": 2, "day": 2, "part": 1, "input": ""}"#);
+        assert_eq!(response["answer"], "ok");
+        assert!(start.elapsed() < Duration::from_millis(300));
+
+        let response = hung.join().unwrap();
+        assert_eq!(response["error"], "day 1: no answer within 0.5s");
+        assert_eq!(response["id"], 1);
+        assert!(pool.slot(1).unwrap().lock().unwrap().is_none());
+
+        // the next request starts a fresh worker
+        let response = pool.handle(r#"{"id": 3, "day": 1, "part": 1, "input": ""}"#);
+        assert_eq!(response["error"], "day 1: no answer within 0.5s");
+    }
+}
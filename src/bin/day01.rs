@@ -1,8 +1,26 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Params, Result, Rng};
+use failure::{bail, format_err};
+use itertools::Itertools;
 use std::collections::HashSet;
+use std::fmt;
+use std::io::BufRead;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2)
+        .generator(&generate)
+        .mode("stats", &stats)
+        .dispatch()
+}
+
+/// `size` non-zero frequency changes.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    (0..size.max(1))
+        .map(|_| match rng.range(-20, 19) {
+            n if n >= 0 => n + 1,
+            n => n,
+        })
+        .map(|n| format!("{:+}", n))
+        .join("\n")
 }
 
 fn part1(input: &str) -> Result<i32> {
@@ -12,24 +30,181 @@ fn part1(input: &str) -> Result<i32> {
         .sum())
 }
 
-fn part2(input: &str) -> Result<i32> {
-    let mut freq = 0;
-    let mut seen = HashSet::<i32>::new();
-    seen.insert(freq);
-    for value in input
+fn changes(input: &str) -> Vec<i64> {
+    input
         .split('\n')
-        .filter_map(|x| x.parse::<i32>().ok())
-        .collect::<Vec<_>>()
-        .iter()
-        .cycle()
-    {
-        freq += value;
-        if seen.contains(&freq) {
-            return Ok(freq);
+        .filter_map(|x| x.parse::<i64>().ok())
+        .collect()
+}
+
+/// The first frequency reached twice, on the `line`th change of pass `pass` (both from 0).
+#[derive(Debug, PartialEq)]
+struct Repeat {
+    frequency: i64,
+    pass: usize,
+    line: usize,
+}
+
+impl Repeat {
+    /// The repeat at `step`, counting the changes applied so far.
+    fn at(frequency: i64, step: usize, len: usize) -> Self {
+        Repeat {
+            frequency,
+            pass: (step - 1) / len,
+            line: (step - 1) % len,
+        }
+    }
+}
+
+/// Finds the first repeat without cycling through the changes until it turns up.
+///
+/// After the first pass every frequency `p` the pass went through comes back as `p + k * sum`
+/// on pass `k`. So a later repeat needs two of them in the same residue class modulo
+/// `sum`, and the first one is the smallest `k * len + position` over those pairs.
+fn first_repeat(changes: &[i64]) -> Result<Repeat> {
+    let len = changes.len();
+    if len == 0 {
+        bail!("no frequency changes");
+    }
+
+    let mut seen = HashSet::new();
+    seen.insert(0);
+    let mut prefixes = vec![0];
+    let mut freq = 0;
+    for (i, change) in changes.iter().enumerate() {
+        freq += change;
+        if !seen.insert(freq) {
+            return Ok(Repeat::at(freq, i + 1, len));
+        }
+        prefixes.push(freq);
+    }
+    let sum = prefixes.pop().unwrap();
+
+    // frequencies grouped by residue, in the order `sum` moves them
+    let mut order: Vec<usize> = (0..len).collect();
+    order.sort_by_key(|&i| (prefixes[i].rem_euclid(sum), prefixes[i] * sum.signum()));
+
+    let mut best: Option<(usize, i64)> = None;
+    for pair in order.windows(2) {
+        let (i, j) = (pair[0], pair[1]);
+        if prefixes[i].rem_euclid(sum) != prefixes[j].rem_euclid(sum) {
+            continue;
+        }
+        // `i` catches up with where `j` started after `passes` passes
+        let passes = (prefixes[j] - prefixes[i]) / sum;
+        let step = passes as usize * len + i;
+        if best.is_none_or(|(best_step, _)| step < best_step) {
+            best = Some((step, prefixes[j]));
+        }
+    }
+    match best {
+        Some((step, frequency)) => Ok(Repeat::at(frequency, step, len)),
+        None => bail!("no frequency is ever reached twice"),
+    }
+}
+
+fn part2(input: &str) -> Result<i64> {
+    Ok(first_repeat(&changes(input))?.frequency)
+}
+
+/// Running statistics over a change log, kept up to date as it streams past.
+///
+/// Lines are counted from 1; a `None` line for `min` or `max` means the starting frequency.
+#[derive(Debug, PartialEq)]
+struct Stats {
+    changes: u64,
+    frequency: i64,
+    min: (i64, Option<u64>),
+    max: (i64, Option<u64>),
+    /// Each threshold with where it's first reached, `Some(None)` being the start: at or
+    /// above a positive one, at or below the others.
+    thresholds: Vec<(i64, Option<Option<u64>>)>,
+}
+
+fn reaches(frequency: i64, threshold: i64) -> bool {
+    if threshold > 0 {
+        frequency >= threshold
+    } else {
+        frequency <= threshold
+    }
+}
+
+impl Stats {
+    fn new(thresholds: Vec<i64>) -> Self {
+        Stats {
+            changes: 0,
+            frequency: 0,
+            min: (0, None),
+            max: (0, None),
+            thresholds: thresholds
+                .into_iter()
+                .map(|t| (t, if reaches(0, t) { Some(None) } else { None }))
+                .collect(),
+        }
+    }
+
+    fn push(&mut self, change: i64, line: u64) {
+        self.changes += 1;
+        self.frequency += change;
+        let freq = self.frequency;
+        if freq < self.min.0 {
+            self.min = (freq, Some(line));
+        }
+        if freq > self.max.0 {
+            self.max = (freq, Some(line));
+        }
+        for (threshold, reached) in self.thresholds.iter_mut().filter(|(_, r)| r.is_none()) {
+            if reaches(freq, *threshold) {
+                *reached = Some(Some(line));
+            }
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn at(line: Option<u64>) -> String {
+            line.map_or("start".into(), |line| format!("line {}", line))
+        }
+        writeln!(f, "changes: {}", self.changes)?;
+        writeln!(f, "frequency: {}", self.frequency)?;
+        writeln!(f, "min: {} ({})", self.min.0, at(self.min.1))?;
+        write!(f, "max: {} ({})", self.max.0, at(self.max.1))?;
+        for (threshold, reached) in &self.thresholds {
+            match reached {
+                Some(line) => write!(f, "\nthreshold {}: {}", threshold, at(*line))?,
+                None => write!(f, "\nthreshold {}: never", threshold)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads one line at a time, so the log can be far bigger than memory.
+fn stream_stats(reader: &mut dyn BufRead, thresholds: Vec<i64>) -> Result<Stats> {
+    let mut stats = Stats::new(thresholds);
+    let mut buffer = String::new();
+    for line in 1.. {
+        buffer.clear();
+        if reader.read_line(&mut buffer)? == 0 {
+            break;
+        }
+        let change = buffer.trim_matches(|c: char| c.is_whitespace() || c == '\u{feff}');
+        if change.is_empty() {
+            continue;
         }
-        seen.insert(freq);
+        let change = change
+            .parse()
+            .map_err(|err| format_err!("line {}: {}", line, err))?;
+        stats.push(change, line);
     }
-    unreachable!();
+    Ok(stats)
+}
+
+/// `--mode stats [-o thresholds=100,-50]`
+fn stats(reader: &mut dyn BufRead, params: &Params) -> Result<String> {
+    let thresholds = params.get_list("thresholds", vec![])?;
+    Ok(stream_stats(reader, thresholds)?.to_string())
 }
 
 #[cfg(test)]
@@ -52,4 +227,108 @@ mod tests {
         assert_eq!(part2(&"+7, +7, -2, -7, -4".replace(", ", "\n"))?, 14);
         Ok(())
     }
+
+    #[test]
+    fn test_first_repeat() -> Result<()> {
+        assert_eq!(
+            first_repeat(&[3, 3, 4, -2, -4])?,
+            Repeat {
+                frequency: 10,
+                pass: 1,
+                line: 1
+            }
+        );
+        assert_eq!(
+            first_repeat(&[1, -1])?,
+            Repeat {
+                frequency: 0,
+                pass: 0,
+                line: 1
+            }
+        );
+        assert_eq!(
+            first_repeat(&[7, 7, -2, -7, -4])?,
+            Repeat {
+                frequency: 14,
+                pass: 2,
+                line: 2
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_repeat() {
+        for input in &["+1", "+1\n+2", "-5\n+2", ""] {
+            assert!(part2(input).is_err());
+        }
+    }
+
+    #[test]
+    fn test_matches_cycling() -> Result<()> {
+        fn cycle(changes: &[i64]) -> Repeat {
+            let mut seen = HashSet::new();
+            seen.insert(0);
+            let mut freq = 0;
+            for step in 1.. {
+                freq += changes[(step - 1) % changes.len()];
+                if !seen.insert(freq) {
+                    return Repeat::at(freq, step, changes.len());
+                }
+            }
+            unreachable!()
+        }
+        for seed in 0..50 {
+            let changes = changes(&generate(&mut Rng::new(seed), 10));
+            if let Ok(repeat) = first_repeat(&changes) {
+                assert_eq!(repeat, cycle(&changes));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_stats() -> Result<()> {
+        let mut log = "+3\r\n-7\n\n+1\n+5\n".as_bytes();
+        let stats = stream_stats(&mut log, vec![5, -4, 100])?;
+        assert_eq!(
+            stats,
+            Stats {
+                changes: 4,
+                frequency: 2,
+                min: (-4, Some(2)),
+                max: (3, Some(1)),
+                thresholds: vec![(5, None), (-4, Some(Some(2))), (100, None)],
+            }
+        );
+        let stats = stream_stats(&mut "+1\n-1".as_bytes(), vec![0, 1, -1])?;
+        assert_eq!(
+            stats.thresholds,
+            vec![(0, Some(None)), (1, Some(Some(1))), (-1, None)]
+        );
+        assert!(stats
+            .to_string()
+            .ends_with("threshold 0: start\nthreshold 1: line 1\nthreshold -1: never"));
+        assert!(stream_stats(&mut "+1\nx".as_bytes(), vec![]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats_mode() -> Result<()> {
+        let input = generate(&mut Rng::new(32), 200);
+        let params = Params::default();
+        let report = stats(&mut input.as_bytes(), &params)?;
+        let frequency = format!("frequency: {}", part1(&input)?);
+        assert!(report.lines().any(|line| line == frequency));
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let input = generate(&mut Rng::new(1), 50);
+        assert_eq!(input.split('\n').count(), 50);
+        let total: i32 = input.split('\n').map(|x| x.parse::<i32>().unwrap()).sum();
+        assert_eq!(part1(&input)?, total);
+        Ok(())
+    }
 }
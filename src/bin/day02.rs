@@ -1,109 +1,187 @@
-use aoc2018::{dispatch, Result};
-use std::collections::HashMap;
-use std::ops;
+use aoc2018::{Day, Params, Result, Rng};
+use failure::err_msg;
+use itertools::Itertools;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, BufRead};
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2)
+        .generator(&generate)
+        .mode("checksum", &checksum)
+        .mode("near", &near)
+        .dispatch()
 }
 
-#[derive(Debug, PartialEq)]
-struct Found {
-    found2: u32,
-    found3: u32,
+/// `size` box IDs, two of which differ by exactly one character.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    let random_id =
+        |rng: &mut Rng| -> Vec<u8> { (0..26).map(|_| b'a' + rng.below(26) as u8).collect() };
+    let mut ids: Vec<Vec<u8>> = (0..size.max(2) - 1).map(|_| random_id(rng)).collect();
+    let mut near = ids[0].clone();
+    let pos = rng.below(near.len());
+    near[pos] = b'a' + ((near[pos] - b'a') as usize + 1 + rng.below(25)) as u8 % 26;
+    ids.push(near);
+    rng.shuffle(&mut ids);
+    ids.into_iter()
+        .map(|id| String::from_utf8(id).expect("ascii"))
+        .join("\n")
 }
 
-impl Found {
-    fn new(found2: u32, found3: u32) -> Self {
-        Found {
-            found2: found2,
-            found3: found3,
-        }
-    }
+/// For each multiplicity asked about, the IDs with some letter appearing exactly that often.
+#[derive(Debug, PartialEq)]
+struct Multiplicities {
+    ids: BTreeMap<usize, Vec<String>>,
 }
 
-impl ops::Add for Found {
-    type Output = Found;
-
-    fn add(self, other: Found) -> Found {
-        Found {
-            found2: self.found2 + other.found2,
-            found3: self.found3 + other.found3,
-        }
+impl Multiplicities {
+    fn count(&self, multiplicity: usize) -> usize {
+        self.ids.get(&multiplicity).map_or(0, |ids| ids.len())
     }
-}
 
-impl ops::AddAssign for Found {
-    fn add_assign(&mut self, other: Found) {
-        *self = Found {
-            found2: self.found2 + other.found2,
-            found3: self.found3 + other.found3,
-        }
+    /// The product of the counts: the puzzle's checksum when asked about 2 and 3.
+    fn checksum(&self) -> usize {
+        self.ids.keys().map(|&n| self.count(n)).product()
     }
 }
 
-fn find(input: &str) -> Found {
+/// Which of the `wanted` multiplicities some letter of `id` has.
+fn find(id: &str, wanted: &[usize]) -> Vec<usize> {
     let mut chars = HashMap::new();
-    for c in input.chars() {
-        let count = chars.entry(c).or_insert(0);
-        *count += 1;
+    for c in id.chars() {
+        *chars.entry(c).or_insert(0) += 1;
     }
-    let mut found2 = 0;
-    let mut found3 = 0;
-    for v in chars.values() {
-        match v {
-            2 => found2 = 1,
-            3 => found3 = 1,
-            _ => {}
+    wanted
+        .iter()
+        .filter(|&n| chars.values().any(|v| v == n))
+        .cloned()
+        .collect()
+}
+
+fn multiplicities<I>(ids: I, wanted: &[usize]) -> Multiplicities
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut found: BTreeMap<usize, Vec<String>> = wanted.iter().map(|&n| (n, vec![])).collect();
+    for id in ids {
+        let id = id.as_ref();
+        for n in find(id, wanted) {
+            found.get_mut(&n).expect("wanted").push(id.into());
         }
     }
-    Found { found2, found3 }
+    Multiplicities { ids: found }
 }
 
-fn part1(input: &str) -> Result<u32> {
-    let mut found = Found::new(0, 0);
-    for row in input.split('\n') {
-        found += find(row);
-    }
-    Ok(found.found2 * found.found3)
+fn part1(input: &str) -> Result<usize> {
+    Ok(multiplicities(input.split('\n'), &[2, 3]).checksum())
+}
+
+/// `--mode checksum [-o multiplicities=2,3]`: the counts, the IDs behind them and the checksum.
+fn checksum(reader: &mut dyn BufRead, params: &Params) -> Result<String> {
+    let wanted = params.get_list("multiplicities", vec![2, 3])?;
+    let ids = reader.lines().collect::<io::Result<Vec<_>>>()?;
+    let found = multiplicities(ids.iter().map(|id| id.trim()), &wanted);
+    let mut report: Vec<_> = found
+        .ids
+        .iter()
+        .map(|(n, ids)| format!("{}: {} ({})", n, ids.len(), ids.join(", ")))
+        .collect();
+    report.push(format!("checksum: {}", found.checksum()));
+    Ok(report.join("\n"))
+}
+
+/// Two IDs (by index) at most the search distance apart, and the letters they share.
+#[derive(Debug, PartialEq)]
+struct NearMatch {
+    a: usize,
+    b: usize,
+    distance: usize,
+    common: String,
+}
+
+fn hamming(s1: &[u8], s2: &[u8]) -> usize {
+    s1.iter().zip(s2).filter(|(c1, c2)| c1 != c2).count()
 }
 
 fn compare(s1: &str, s2: &str) -> bool {
-    let mut found = false;
-    for (c1, c2) in s1.chars().zip(s2.chars()) {
-        if c1 != c2 {
-            if found {
-                return false;
-            }
-            found = true;
-        }
-    }
-    found
+    s1.len() == s2.len() && hamming(s1.as_bytes(), s2.as_bytes()) == 1
 }
 
-fn find_match(input: &str) -> (&str, &str) {
-    let mut strings = vec![];
-    for row in input.split('\n') {
-        strings.push(row);
+/// All pairs of equally long IDs differing in at most `max_distance` positions.
+///
+/// Each ID is cut into `max_distance + 1` blocks. Two IDs that close can't differ in every
+/// block, so only IDs sharing some block exactly need comparing, which keeps the search
+/// close to linear instead of comparing every pair.
+fn near_matches(ids: &[&str], max_distance: usize) -> Vec<NearMatch> {
+    let blocks = max_distance + 1;
+    let mut index: HashMap<(usize, usize, &[u8]), Vec<usize>> = HashMap::new();
+    for (i, id) in ids.iter().enumerate() {
+        let id = id.as_bytes();
+        for block in 0..blocks {
+            let range = block * id.len() / blocks..(block + 1) * id.len() / blocks;
+            index
+                .entry((id.len(), block, &id[range]))
+                .or_default()
+                .push(i);
+        }
     }
-    for a in 0..strings.len() {
-        for b in (a + 1)..strings.len() {
-            if compare(strings[a], strings[b]) {
-                return (strings[a], strings[b]);
+
+    let mut pairs = HashSet::new();
+    for bucket in index.values() {
+        for (n, &a) in bucket.iter().enumerate() {
+            for &b in &bucket[n + 1..] {
+                pairs.insert((a, b));
             }
         }
     }
-    unreachable!();
+
+    let mut matches: Vec<_> = pairs
+        .into_iter()
+        .filter_map(|(a, b)| {
+            let (s1, s2) = (ids[a].as_bytes(), ids[b].as_bytes());
+            let distance = hamming(s1, s2);
+            if distance > max_distance {
+                return None;
+            }
+            let common = s1
+                .iter()
+                .zip(s2)
+                .filter(|(c1, c2)| c1 == c2)
+                .map(|(&c, _)| c as char)
+                .collect();
+            Some(NearMatch {
+                a,
+                b,
+                distance,
+                common,
+            })
+        })
+        .collect();
+    matches.sort_by_key(|m| (m.a, m.b));
+    matches
 }
 
 fn part2(input: &str) -> Result<String> {
-    let mut chars = vec![];
-    let (s1, s2) = find_match(input);
-    for (c1, c2) in s1.chars().zip(s2.chars()) {
-        if c1 == c2 {
-            chars.push(c1);
-        }
-    }
-    Ok(chars.iter().collect())
+    let ids: Vec<_> = input.split('\n').collect();
+    near_matches(&ids, 1)
+        .into_iter()
+        .find(|m| compare(ids[m.a], ids[m.b]))
+        .map(|m| m.common)
+        .ok_or_else(|| err_msg("no two IDs differ by exactly one character"))
+}
+
+/// `--mode near [-o distance=1]`: every close pair, one per line.
+fn near(reader: &mut dyn BufRead, params: &Params) -> Result<String> {
+    let ids = reader
+        .lines()
+        .map(|line| Ok(line?.trim().to_string()))
+        .filter(|id: &Result<String>| id.as_ref().map_or(true, |id| !id.is_empty()))
+        .collect::<Result<Vec<_>>>()?;
+    let ids: Vec<&str> = ids.iter().map(|id| id.as_str()).collect();
+    Ok(near_matches(&ids, params.get("distance", 1)?)
+        .into_iter()
+        .map(|m| format!("{} {} {} {}", ids[m.a], ids[m.b], m.distance, m.common))
+        .join("\n"))
 }
 
 #[cfg(test)]
@@ -112,13 +190,30 @@ mod tests {
 
     #[test]
     fn test_find() {
-        assert_eq!(find("abcdef"), Found::new(0, 0));
-        assert_eq!(find("bababc"), Found::new(1, 1));
-        assert_eq!(find("abbcde"), Found::new(1, 0));
-        assert_eq!(find("abcccd"), Found::new(0, 1));
-        assert_eq!(find("aabcdd"), Found::new(1, 0));
-        assert_eq!(find("abcdee"), Found::new(1, 0));
-        assert_eq!(find("ababab"), Found::new(0, 1));
+        let wanted = [2, 3];
+        assert!(find("abcdef", &wanted).is_empty());
+        assert_eq!(find("bababc", &wanted), vec![2, 3]);
+        assert_eq!(find("abbcde", &wanted), vec![2]);
+        assert_eq!(find("abcccd", &wanted), vec![3]);
+        assert_eq!(find("aabcdd", &wanted), vec![2]);
+        assert_eq!(find("abcdee", &wanted), vec![2]);
+        assert_eq!(find("ababab", &wanted), vec![3]);
+    }
+
+    #[test]
+    fn test_part1() -> Result<()> {
+        let input = "abcdef\nbababc\nabbcde\nabcccd\naabcdd\nabcdee\nababab";
+        Ok(assert_eq!(part1(input)?, 12))
+    }
+
+    #[test]
+    fn test_multiplicities() {
+        let found = multiplicities(vec!["aaaab", "aabbb", "abcd"], &[1, 3, 4]);
+        assert_eq!(found.count(1), 2);
+        assert_eq!(found.count(3), 1);
+        assert_eq!(found.ids[&4], vec!["aaaab".to_string()]);
+        assert_eq!(found.count(2), 0);
+        assert_eq!(found.checksum(), 2);
     }
 
     #[test]
@@ -130,4 +225,54 @@ mod tests {
     fn test_compare_true() {
         assert!(compare("fghij", "fguij"),);
     }
+
+    #[test]
+    fn test_near_matches() {
+        let ids = [
+            "abcde", "fghij", "klmno", "pqrst", "fguij", "axcye", "wvxyz", "abcdx",
+        ];
+        let found: Vec<_> = near_matches(&ids, 1)
+            .into_iter()
+            .map(|m| (m.a, m.b, m.distance, m.common))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(0, 7, 1, "abcd".into()), (1, 4, 1, "fgij".into())]
+        );
+        assert_eq!(near_matches(&ids, 2).len(), 3);
+        assert_eq!(near_matches(&["ab", "ab", "abc"], 0).len(), 1);
+    }
+
+    #[test]
+    fn test_near_matches_all_pairs() {
+        let input = generate(&mut Rng::new(33), 60);
+        let ids: Vec<&str> = input.split('\n').collect();
+        for max_distance in 0..4 {
+            let expected: Vec<_> = (0..ids.len())
+                .tuple_combinations()
+                .filter(|&(a, b)| hamming(ids[a].as_bytes(), ids[b].as_bytes()) <= max_distance)
+                .collect();
+            let found: Vec<_> = near_matches(&ids, max_distance)
+                .into_iter()
+                .map(|m| (m.a, m.b))
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_part2() -> Result<()> {
+        let input = "abcde\nfghij\nklmno\npqrst\nfguij\naxcye\nwvxyz";
+        assert_eq!(part2(input)?, "fgij");
+        assert!(part2("abc\nxyz").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let input = generate(&mut Rng::new(2), 20);
+        assert_eq!(input.split('\n').count(), 20);
+        assert_eq!(part2(&input)?.len(), 25);
+        Ok(())
+    }
 }
@@ -1,10 +1,10 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Params, Result, Rng};
 use failure::{err_msg, Error};
-use itertools::{Itertools, Product};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::{Captures, Regex};
-use std::collections::HashMap;
-use std::ops::Range;
+use std::fmt;
+use std::io::BufRead;
 use std::str::FromStr;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -17,8 +17,35 @@ struct Claim {
 }
 
 impl Claim {
-    fn walk(&self) -> Product<Range<usize>, Range<usize>> {
-        (self.left..(self.left + self.width)).cartesian_product(self.top..(self.top + self.height))
+    fn random(rng: &mut Rng, id: usize) -> Self {
+        Claim {
+            id,
+            top: rng.below(990),
+            left: rng.below(990),
+            width: 1 + rng.below(29),
+            height: 1 + rng.below(29),
+        }
+    }
+
+    fn right(&self) -> usize {
+        self.left + self.width
+    }
+
+    fn bottom(&self) -> usize {
+        self.top + self.height
+    }
+
+    /// The area of fabric both claims cover.
+    fn overlap(&self, other: &Claim) -> usize {
+        let width = self
+            .right()
+            .min(other.right())
+            .saturating_sub(self.left.max(other.left));
+        let height = self
+            .bottom()
+            .min(other.bottom())
+            .saturating_sub(self.top.max(other.top));
+        width * height
     }
 }
 
@@ -51,44 +78,383 @@ impl FromStr for Claim {
     }
 }
 
+impl fmt::Display for Claim {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "#{} @ {},{}: {}x{}",
+            self.id, self.left, self.top, self.width, self.height
+        )
+    }
+}
+
+/// `size` claims on a 1000x1000 fabric.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    (1..=size.max(1))
+        .map(|id| Claim::random(rng, id).to_string())
+        .join("\n")
+}
+
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2)
+        .generator(&generate)
+        .mode("conflicts", &conflicts)
+        .mode("dot", &dot)
+        .mode("heatmap", &heatmap)
+        .dispatch()
 }
 
-fn part1(input: &str) -> Result<usize> {
-    let mut fabric = HashMap::new();
-    for row in input.split('\n') {
-        let claim: Claim = row.parse()?;
-        for point in claim.walk() {
-            let count = fabric.entry(point).or_insert(0);
-            *count += 1;
+fn parse_claims(input: &str) -> Result<Vec<Claim>> {
+    input.split('\n').map(|row| row.parse()).collect()
+}
+
+/// How often each stretch between consecutive `ys` is covered, as a segment tree.
+///
+/// `once` and `twice` hold how much of a node's stretch is covered at least once and at
+/// least twice, counting only the rectangles added at that node or below it.
+struct CoverTree {
+    ys: Vec<usize>,
+    count: Vec<u32>,
+    once: Vec<usize>,
+    twice: Vec<usize>,
+}
+
+impl CoverTree {
+    fn new(ys: Vec<usize>) -> Self {
+        let nodes = 4 * ys.len().max(1);
+        CoverTree {
+            ys,
+            count: vec![0; nodes],
+            once: vec![0; nodes],
+            twice: vec![0; nodes],
+        }
+    }
+
+    fn segment(&self, y: usize) -> usize {
+        self.ys.binary_search(&y).expect("y not in the tree")
+    }
+
+    /// Adds (or with `remove`, takes away) cover of `top..bottom`.
+    fn update(&mut self, top: usize, bottom: usize, remove: bool) {
+        let (lo, hi) = (self.segment(top), self.segment(bottom));
+        if lo < hi {
+            self.update_node(1, 0, self.ys.len() - 1, lo, hi, remove);
         }
     }
 
-    Ok(fabric.values().filter(|&x| *x > 1).count())
+    fn update_node(
+        &mut self,
+        node: usize,
+        start: usize,
+        end: usize,
+        lo: usize,
+        hi: usize,
+        remove: bool,
+    ) {
+        if hi <= start || end <= lo {
+            return;
+        }
+        if lo <= start && end <= hi {
+            if remove {
+                self.count[node] -= 1;
+            } else {
+                self.count[node] += 1;
+            }
+        } else {
+            let mid = (start + end) / 2;
+            self.update_node(2 * node, start, mid, lo, hi, remove);
+            self.update_node(2 * node + 1, mid, end, lo, hi, remove);
+        }
+        self.pull(node, start, end);
+    }
+
+    fn pull(&mut self, node: usize, start: usize, end: usize) {
+        let full = self.ys[end] - self.ys[start];
+        let leaf = end - start == 1;
+        let (child_once, child_twice) = if leaf {
+            (0, 0)
+        } else {
+            (
+                self.once[2 * node] + self.once[2 * node + 1],
+                self.twice[2 * node] + self.twice[2 * node + 1],
+            )
+        };
+        let (once, twice) = match self.count[node] {
+            0 => (child_once, child_twice),
+            1 => (full, child_once),
+            _ => (full, full),
+        };
+        self.once[node] = once;
+        self.twice[node] = twice;
+    }
+
+    fn covered_twice(&self) -> usize {
+        self.twice[1]
+    }
+}
+
+/// The area covered by two or more claims, sweeping across x with the y cover in a tree.
+fn overlap_area(claims: &[Claim]) -> usize {
+    let mut events: Vec<(usize, bool, &Claim)> = claims
+        .iter()
+        .flat_map(|c| vec![(c.left, false, c), (c.right(), true, c)])
+        .collect();
+    events.sort_by_key(|&(x, remove, _)| (x, remove));
+
+    let mut ys = claims.iter().flat_map(|c| vec![c.top, c.bottom()]).sorted();
+    ys.dedup();
+    let mut tree = CoverTree::new(ys);
+
+    let mut area = 0;
+    let mut prev_x = 0;
+    for (x, remove, claim) in events {
+        area += tree.covered_twice() * (x - prev_x);
+        prev_x = x;
+        tree.update(claim.top, claim.bottom(), remove);
+    }
+    area
+}
+
+/// For each query `q`, how many points `p` have `p.0 <= q.0` and `p.1 <= q.1`.
+///
+/// Offline: points are added to a Fenwick tree over the second coordinate in order of
+/// the first, with each query answered once all the points left of it are in.
+fn dominance(points: &[(i64, i64)], queries: &[(i64, i64)]) -> Vec<usize> {
+    let mut seconds = points.iter().map(|p| p.1).sorted();
+    seconds.dedup();
+    let mut fenwick = vec![0; seconds.len() + 1];
+
+    let mut points: Vec<_> = points.to_vec();
+    points.sort();
+    let mut order: Vec<usize> = (0..queries.len()).collect();
+    order.sort_by_key(|&i| queries[i]);
+
+    let mut counts = vec![0; queries.len()];
+    let mut next = 0;
+    for i in order {
+        let (first, second) = queries[i];
+        while next < points.len() && points[next].0 <= first {
+            let mut pos = seconds.binary_search(&points[next].1).expect("point") + 1;
+            while pos < fenwick.len() {
+                fenwick[pos] += 1;
+                pos += pos & pos.wrapping_neg();
+            }
+            next += 1;
+        }
+        let mut pos = seconds.partition_point(|&s| s <= second);
+        while pos > 0 {
+            counts[i] += fenwick[pos];
+            pos -= pos & pos.wrapping_neg();
+        }
+    }
+    counts
+}
+
+/// How many other claims each claim overlaps, without comparing every pair.
+///
+/// Another claim misses this one if it lies entirely left (L), right (R), above (A) or
+/// below (B) of it. L and R can't both hold, nor can A and B, so by inclusion-exclusion
+/// the misses are L + R + A + B - LA - LB - RA - RB, each a dominance count.
+fn overlap_counts(claims: &[Claim]) -> Vec<usize> {
+    type Corner = fn(&Claim) -> (i64, i64);
+    let count = |point: Corner, query: Corner| {
+        let points: Vec<_> = claims.iter().map(point).collect();
+        let queries: Vec<_> = claims.iter().map(query).collect();
+        dominance(&points, &queries)
+    };
+    fn l(c: &Claim) -> i64 {
+        c.left as i64
+    }
+    fn r(c: &Claim) -> i64 {
+        c.right() as i64
+    }
+    fn t(c: &Claim) -> i64 {
+        c.top as i64
+    }
+    fn b(c: &Claim) -> i64 {
+        c.bottom() as i64
+    }
+    let counts: Vec<Vec<usize>> = vec![
+        count(|c| (r(c), 0), |c| (l(c), 0)),
+        count(|c| (-l(c), 0), |c| (-r(c), 0)),
+        count(|c| (b(c), 0), |c| (t(c), 0)),
+        count(|c| (-t(c), 0), |c| (-b(c), 0)),
+        count(|c| (r(c), b(c)), |c| (l(c), t(c))),
+        count(|c| (r(c), -t(c)), |c| (l(c), -b(c))),
+        count(|c| (-l(c), b(c)), |c| (-r(c), t(c))),
+        count(|c| (-l(c), -t(c)), |c| (-r(c), -b(c))),
+    ];
+    (0..claims.len())
+        .map(|i| {
+            let misses = counts[..4].iter().map(|c| c[i]).sum::<usize>()
+                - counts[4..].iter().map(|c| c[i]).sum::<usize>();
+            (claims.len() - 1).saturating_sub(misses)
+        })
+        .collect()
+}
+
+fn part1(input: &str) -> Result<usize> {
+    Ok(overlap_area(&parse_claims(input)?))
 }
 
 fn part2(input: &str) -> Result<usize> {
-    let mut fabric = HashMap::new();
-    for row in input.split('\n') {
-        let claim: Claim = row.parse()?;
-        for point in claim.walk() {
-            let count = fabric.entry(point).or_insert(0);
-            *count += 1;
+    let claims = parse_claims(input)?;
+    claims
+        .iter()
+        .zip(overlap_counts(&claims))
+        .find(|&(_, overlaps)| overlaps == 0)
+        .map(|(claim, _)| claim.id)
+        .ok_or_else(|| err_msg("every claim overlaps another"))
+}
+
+/// Another claim (by index) that a claim overlaps, and by how much.
+#[derive(Debug, PartialEq)]
+struct Conflict {
+    other: usize,
+    area: usize,
+}
+
+/// Every claim's conflicts, in input order, with each list ordered by the other claim.
+///
+/// Claims are swept by left edge, so each one is only checked against the claims still
+/// open at that x.
+fn conflict_graph(claims: &[Claim]) -> Vec<Vec<Conflict>> {
+    let mut graph: Vec<Vec<Conflict>> = claims.iter().map(|_| vec![]).collect();
+    let order = (0..claims.len()).sorted_by_key(|&i| claims[i].left);
+    let mut open: Vec<usize> = vec![];
+    for i in order {
+        let claim = &claims[i];
+        open.retain(|&j| claims[j].right() > claim.left);
+        for &j in &open {
+            let area = claim.overlap(&claims[j]);
+            if area > 0 {
+                graph[i].push(Conflict { other: j, area });
+                graph[j].push(Conflict { other: i, area });
+            }
         }
+        open.push(i);
     }
+    for conflicts in &mut graph {
+        conflicts.sort_by_key(|c| c.other);
+    }
+    graph
+}
+
+/// The conflict graph for Graphviz, one node per claim and edges labelled by area.
+fn to_dot(claims: &[Claim], graph: &[Vec<Conflict>]) -> String {
+    let mut lines = vec!["graph claims {".to_string()];
+    for (i, conflicts) in graph.iter().enumerate() {
+        lines.push(format!("  {};", claims[i].id));
+        for conflict in conflicts.iter().filter(|c| c.other > i) {
+            lines.push(format!(
+                "  {} -- {} [label={}];",
+                claims[i].id, claims[conflict.other].id, conflict.area
+            ));
+        }
+    }
+    lines.push("}".into());
+    lines.join("\n")
+}
+
+/// How many claims cover each square inch, up to the furthest claim edge.
+fn coverage(claims: &[Claim]) -> Vec<Vec<u32>> {
+    let width = claims.iter().map(|c| c.right()).max().unwrap_or(0);
+    let height = claims.iter().map(|c| c.bottom()).max().unwrap_or(0);
+    // mark the corners, then sum along rows and columns
+    let mut counts = vec![vec![0i64; width + 1]; height + 1];
+    for c in claims {
+        counts[c.top][c.left] += 1;
+        counts[c.top][c.right()] -= 1;
+        counts[c.bottom()][c.left] -= 1;
+        counts[c.bottom()][c.right()] += 1;
+    }
+    for y in 0..=height {
+        for x in 0..=width {
+            let above = if y > 0 { counts[y - 1][x] } else { 0 };
+            let left = if x > 0 { counts[y][x - 1] } else { 0 };
+            let diagonal = if x > 0 && y > 0 {
+                counts[y - 1][x - 1]
+            } else {
+                0
+            };
+            counts[y][x] += above + left - diagonal;
+        }
+    }
+    counts
+        .iter()
+        .take(height)
+        .map(|row| row.iter().take(width).map(|&n| n as u32).collect())
+        .collect()
+}
+
+/// Coverage as a plain PPM image: black where nothing is claimed, blue for a single
+/// claim, and yellow through red as the overlaps pile up.
+fn to_ppm(coverage: &[Vec<u32>]) -> String {
+    let height = coverage.len();
+    let width = coverage.first().map_or(0, |row| row.len());
+    let most = coverage
+        .iter()
+        .flat_map(|row| row.iter())
+        .cloned()
+        .max()
+        .unwrap_or(0);
+    let colour = |n: u32| match n {
+        0 => (0, 0, 0),
+        1 => (40, 80, 200),
+        _ => (255, 255 - 255 * (n - 2) / (most - 1).max(1), 0),
+    };
+    let mut lines = vec![format!("P3\n{} {}\n255", width, height)];
+    for row in coverage {
+        lines.push(
+            row.iter()
+                .map(|&n| {
+                    let (r, g, b) = colour(n);
+                    format!("{} {} {}", r, g, b)
+                })
+                .join(" "),
+        );
+    }
+    lines.join("\n")
+}
 
-    for row in input.split('\n') {
-        let claim: Claim = row.parse()?;
-        if claim
-            .walk()
-            .filter_map(|point| fabric.get(&point))
-            .all(|&count| count == 1)
-        {
-            return Ok(claim.id);
+fn read_claims(reader: &mut dyn BufRead) -> Result<Vec<Claim>> {
+    let mut claims = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            claims.push(line.trim().parse()?);
         }
     }
-    Ok(0)
+    Ok(claims)
+}
+
+fn conflicts(reader: &mut dyn BufRead, _params: &Params) -> Result<String> {
+    let claims = read_claims(reader)?;
+    let graph = conflict_graph(&claims);
+    Ok(graph
+        .iter()
+        .enumerate()
+        .map(|(i, conflicts)| {
+            let others = conflicts
+                .iter()
+                .map(|c| format!("#{} ({})", claims[c.other].id, c.area))
+                .join(", ");
+            if others.is_empty() {
+                format!("#{}: none", claims[i].id)
+            } else {
+                format!("#{}: {}", claims[i].id, others)
+            }
+        })
+        .join("\n"))
+}
+
+fn dot(reader: &mut dyn BufRead, _params: &Params) -> Result<String> {
+    let claims = read_claims(reader)?;
+    Ok(to_dot(&claims, &conflict_graph(&claims)))
+}
+
+fn heatmap(reader: &mut dyn BufRead, _params: &Params) -> Result<String> {
+    Ok(to_ppm(&coverage(&read_claims(reader)?)))
 }
 
 #[cfg(test)]
@@ -110,4 +476,105 @@ mod tests {
         );
         Ok(())
     }
+
+    const INPUT: &str = "#1 @ 1,3: 4x4\n#2 @ 3,1: 4x4\n#3 @ 5,5: 2x2";
+
+    #[test]
+    fn test_part1() -> Result<()> {
+        Ok(assert_eq!(part1(INPUT)?, 4))
+    }
+
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(INPUT)?, 3);
+        assert!(part2("#1 @ 0,0: 2x2\n#2 @ 1,1: 2x2").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_against_grid() -> Result<()> {
+        for seed in 0..20 {
+            let claims = parse_claims(&generate(&mut Rng::new(seed), 300))?;
+            let mut fabric = vec![vec![0; 1020]; 1020];
+            for c in &claims {
+                for row in &mut fabric[c.top..c.bottom()] {
+                    for square in &mut row[c.left..c.right()] {
+                        *square += 1;
+                    }
+                }
+            }
+            let area = fabric
+                .iter()
+                .flat_map(|row| row.iter())
+                .filter(|&&n| n > 1)
+                .count();
+            assert_eq!(overlap_area(&claims), area);
+
+            let overlaps = |a: &Claim, b: &Claim| {
+                a.left < b.right() && b.left < a.right() && a.top < b.bottom() && b.top < a.bottom()
+            };
+            let counts: Vec<_> = claims
+                .iter()
+                .enumerate()
+                .map(|(i, a)| {
+                    let others = claims.iter().enumerate().filter(|&(j, _)| j != i);
+                    others.filter(|(_, b)| overlaps(a, b)).count()
+                })
+                .collect();
+            assert_eq!(overlap_counts(&claims), counts);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflict_graph() -> Result<()> {
+        let claims = parse_claims(&format!("{}\n#4 @ 2,2: 2x3", INPUT))?;
+        let graph = conflict_graph(&claims);
+        assert_eq!(
+            graph[0],
+            vec![
+                Conflict { other: 1, area: 4 },
+                Conflict { other: 3, area: 4 }
+            ]
+        );
+        assert_eq!(graph[2], vec![]);
+        assert_eq!(graph[3][1], Conflict { other: 1, area: 3 });
+        assert_eq!(
+            to_dot(&claims, &graph),
+            "graph claims {\n  1;\n  1 -- 2 [label=4];\n  1 -- 4 [label=4];\n  2;\n  \
+             2 -- 4 [label=3];\n  3;\n  4;\n}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_coverage() -> Result<()> {
+        let claims = parse_claims(INPUT)?;
+        let counts = coverage(&claims);
+        assert_eq!((counts.len(), counts[0].len()), (7, 7));
+        assert_eq!(counts[3], vec![0, 1, 1, 2, 2, 1, 1]);
+        assert_eq!(counts[6], vec![0, 1, 1, 1, 1, 1, 1]);
+        let image = to_ppm(&counts);
+        assert!(image.starts_with("P3\n7 7\n255\n0 0 0 "));
+        assert_eq!(image.lines().count(), 3 + 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_fabric() -> Result<()> {
+        let input = "#1 @ 0,0: 3000000x3000000\n#2 @ 2000000,2000000: 2000000x2000000";
+        assert_eq!(part1(input)?, 1_000_000_000_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_round_trip() -> Result<()> {
+        let mut rng = Rng::new(3);
+        for id in 1..100 {
+            let claim = Claim::random(&mut rng, id);
+            assert_eq!(claim.to_string().parse::<Claim>()?, claim);
+        }
+        part1(&generate(&mut rng, 20))?;
+        Ok(())
+    }
 }
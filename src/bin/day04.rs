@@ -1,8 +1,11 @@
-use aoc2018::{dispatch, Result};
-use failure::{err_msg, Error};
+use aoc2018::{Day, Params, Result, Rng};
+use failure::{bail, err_msg, format_err, Error};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::{Captures, Regex};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, BufRead};
 use std::str::FromStr;
 
 #[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
@@ -33,12 +36,20 @@ impl FromStr for Record {
             .unwrap();
         }
 
-        let caps = RE.captures(s).unwrap();
+        let caps = RE
+            .captures(s)
+            .ok_or_else(|| format_err!("not a guard record: `{}`", s))?;
         let year = get_cap_int(&caps, "year")?;
         let month = get_cap_int(&caps, "month")?;
         let day = get_cap_int(&caps, "day")?;
         let hour = get_cap_int(&caps, "hour")?;
         let minute = get_cap_int(&caps, "minute")?;
+        if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+            bail!("not a valid date: `{}`", s);
+        }
+        if hour > 23 || minute > 59 {
+            bail!("not a valid time: `{}`", s);
+        }
         fn get_cap_int(caps: &Captures, name: &str) -> Result<u32> {
             Ok(caps
                 .name(name)
@@ -77,43 +88,243 @@ impl FromStr for Record {
     }
 }
 
-fn main() {
-    dispatch(&part1, &part2)
+impl Record {
+    fn date(&self) -> (u32, u32, u32) {
+        (self.year, self.month, self.day)
+    }
+
+    fn timestamp(&self) -> String {
+        format!(
+            "{}-{:02}-{:02} {:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute
+        )
+    }
 }
 
-fn guard_map(input: &str) -> Result<HashMap<u32, [usize; 60]>> {
-    let mut records: Vec<Record> = input
-        .split('\n')
-        .filter_map(|row| row.parse().ok())
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] ", self.timestamp())?;
+        match self.action {
+            Action::StartShift(id) => write!(f, "Guard #{} begins shift", id),
+            Action::WakesUp => write!(f, "wakes up"),
+            Action::FallsAsleep => write!(f, "falls asleep"),
+        }
+    }
+}
+
+const DAYS_IN_MONTH: [u32; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+fn is_leap(year: u32) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+/// Days in a month numbered from 1.
+fn days_in_month(year: u32, month: u32) -> u32 {
+    if month == 2 && is_leap(year) {
+        29
+    } else {
+        DAYS_IN_MONTH[month as usize - 1]
+    }
+}
+
+/// (year, month, day) of the nth day counting from 1518-01-01
+fn date(n: usize) -> (u32, u32, u32) {
+    let mut year = 1518;
+    let mut day = n as u32;
+    while day >= if is_leap(year) { 366 } else { 365 } {
+        day -= if is_leap(year) { 366 } else { 365 };
+        year += 1;
+    }
+    for month in 1..=12 {
+        let days = days_in_month(year, month);
+        if day < days {
+            return (year, month, day + 1);
+        }
+        day -= days;
+    }
+    unreachable!();
+}
+
+/// The day after a valid date.
+fn next_day((year, month, day): (u32, u32, u32)) -> (u32, u32, u32) {
+    if day < days_in_month(year, month) {
+        (year, month, day + 1)
+    } else if month < 12 {
+        (year, month + 1, 1)
+    } else {
+        (year + 1, 1, 1)
+    }
+}
+
+fn record(date: (u32, u32, u32), hour: u32, minute: u32, action: Action) -> Record {
+    let (year, month, day) = date;
+    Record {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        action,
+    }
+}
+
+/// `size` shifts, each on its own night, with the records shuffled as in the puzzle.
+fn generate_records(rng: &mut Rng, size: usize) -> Vec<Record> {
+    let guards: Vec<u32> = (0..size / 4 + 1)
+        .map(|_| rng.range(1, 3500) as u32)
         .collect();
-    records.sort();
+    let mut records = vec![];
+    for night in 1..=size {
+        let guard = *rng.choose(&guards);
+        if rng.one_in(2) {
+            let minute = rng.range(45, 59) as u32;
+            records.push(record(
+                date(night - 1),
+                23,
+                minute,
+                Action::StartShift(guard),
+            ));
+        } else {
+            let minute = rng.range(0, 3) as u32;
+            records.push(record(date(night), 0, minute, Action::StartShift(guard)));
+        }
+        let mut minutes: Vec<u32> = (5..60).collect();
+        rng.shuffle(&mut minutes);
+        let mut changes = minutes[..2 * rng.below(4)].to_vec();
+        changes.sort();
+        for (sleep, wake) in changes.into_iter().tuples() {
+            records.push(record(date(night), 0, sleep, Action::FallsAsleep));
+            records.push(record(date(night), 0, wake, Action::WakesUp));
+        }
+    }
+    rng.shuffle(&mut records);
+    records
+}
 
-    let mut guard_sleeps: HashMap<u32, [usize; 60]> = HashMap::new();
+fn generate(rng: &mut Rng, size: usize) -> String {
+    generate_records(rng, size.max(1))
+        .iter()
+        .map(|record| record.to_string())
+        .join("\n")
+}
+
+fn main() {
+    Day::new(&part1, &part2)
+        .generator(&generate)
+        .mode("report", &report)
+        .dispatch()
+}
+
+/// One guard's night: the date of its midnight hour and the minutes of it spent asleep.
+#[derive(Debug, PartialEq, Clone)]
+struct Shift {
+    guard: u32,
+    date: (u32, u32, u32),
+    asleep: [bool; 60],
+}
+
+impl Shift {
+    fn begin(guard: u32, record: &Record) -> Result<Self> {
+        let date = match record.hour {
+            0 => record.date(),
+            12..=23 => next_day(record.date()),
+            _ => bail!(
+                "[{}] guard #{} begins shift after the midnight hour",
+                record.timestamp(),
+                guard
+            ),
+        };
+        Ok(Shift {
+            guard,
+            date,
+            asleep: [false; 60],
+        })
+    }
+
+    /// Checks a sleep or wake record falls in this shift's midnight hour.
+    fn minute(&self, record: &Record) -> Result<usize> {
+        if record.hour != 0 || record.date() != self.date {
+            let (year, month, day) = self.date;
+            bail!(
+                "[{}] is outside guard #{}'s midnight hour on {}-{:02}-{:02}",
+                record.timestamp(),
+                self.guard,
+                year,
+                month,
+                day
+            );
+        }
+        Ok(record.minute as usize)
+    }
+}
 
-    // let mut guard = 0;
-    let mut sleep = 0;
-    let mut sleeps = &mut [0; 60];
+/// Replays the sorted records, rejecting any that don't fit the current shift.
+fn shifts(mut records: Vec<Record>) -> Result<Vec<Shift>> {
+    records.sort();
+    let mut shifts: Vec<Shift> = vec![];
+    let mut asleep: Option<(Record, usize)> = None;
     for record in records {
-        match record.action {
-            Action::StartShift(id) => {
-                sleeps = guard_sleeps.entry(id).or_insert([0; 60]);
+        if let Action::StartShift(guard) = record.action {
+            if let Some((since, _)) = asleep {
+                bail!(
+                    "[{}] guard #{} begins shift while guard #{} is still asleep (since {})",
+                    record.timestamp(),
+                    guard,
+                    shifts.last().expect("asleep on a shift").guard,
+                    since.timestamp()
+                );
             }
-            Action::FallsAsleep => sleep = record.minute as usize,
-            Action::WakesUp => {
-                let wake = record.minute as usize;
-                if sleep <= wake {
-                    for min in sleep..wake {
-                        sleeps[min] += 1;
-                    }
-                } else {
-                    for min in sleep..60 {
-                        sleeps[min] += 1;
-                    }
-                    for min in 0..wake {
-                        sleeps[min] += 1;
-                    }
+            shifts.push(Shift::begin(guard, &record)?);
+            continue;
+        }
+        let shift = match shifts.last_mut() {
+            Some(shift) => shift,
+            None => bail!("[{}] record before any shift begins", record.timestamp()),
+        };
+        let minute = shift.minute(&record)?;
+        match (&record.action, asleep.take()) {
+            (Action::FallsAsleep, None) => asleep = Some((record, minute)),
+            (Action::FallsAsleep, Some((since, _))) => bail!(
+                "[{}] guard #{} falls asleep while already asleep (since {})",
+                record.timestamp(),
+                shift.guard,
+                since.timestamp()
+            ),
+            (Action::WakesUp, Some((_, sleep))) => {
+                for asleep in &mut shift.asleep[sleep..minute] {
+                    *asleep = true;
                 }
             }
+            (Action::WakesUp, None) => bail!(
+                "[{}] guard #{} wakes up without falling asleep",
+                record.timestamp(),
+                shift.guard
+            ),
+            (Action::StartShift(_), _) => unreachable!(),
+        }
+    }
+    if let Some((since, _)) = asleep {
+        bail!(
+            "[{}] guard #{} falls asleep and never wakes up",
+            since.timestamp(),
+            shifts.last().expect("asleep on a shift").guard
+        );
+    }
+    Ok(shifts)
+}
+
+fn parse_records(input: &str) -> Result<Vec<Record>> {
+    input.split('\n').map(|row| row.parse()).collect()
+}
+
+fn guard_map(input: &str) -> Result<HashMap<u32, [usize; 60]>> {
+    let mut guard_sleeps: HashMap<u32, [usize; 60]> = HashMap::new();
+    for shift in shifts(parse_records(input)?)? {
+        let sleeps = guard_sleeps.entry(shift.guard).or_insert([0; 60]);
+        for (minute, &asleep) in shift.asleep.iter().enumerate() {
+            if asleep {
+                sleeps[minute] += 1;
+            }
         }
     }
     Ok(guard_sleeps)
@@ -147,6 +358,143 @@ fn part2(input: &str) -> Result<u32> {
     Ok(longest_sleeper * minute as u32)
 }
 
+/// What the report says about one guard.
+#[derive(Debug, PartialEq)]
+struct GuardStats {
+    guard: u32,
+    shifts: usize,
+    asleep: usize,
+    /// The minute most often spent asleep and how many times, if the guard ever slept.
+    most_slept: Option<(usize, usize)>,
+}
+
+/// Per guard, in order of ID.
+fn guard_stats(shifts: &[Shift]) -> Vec<GuardStats> {
+    let mut guards: BTreeMap<u32, (usize, [usize; 60])> = BTreeMap::new();
+    for shift in shifts {
+        let (count, sleeps) = guards.entry(shift.guard).or_insert((0, [0; 60]));
+        *count += 1;
+        for (minute, &asleep) in shift.asleep.iter().enumerate() {
+            if asleep {
+                sleeps[minute] += 1;
+            }
+        }
+    }
+    guards
+        .into_iter()
+        .map(|(guard, (shifts, sleeps))| {
+            let asleep = sleeps.iter().sum();
+            // the earliest of the most slept minutes
+            let most_slept = (0..60)
+                .map(|minute| (minute, sleeps[minute]))
+                .min_by_key(|&(minute, times)| (std::cmp::Reverse(times), minute))
+                .filter(|_| asleep > 0);
+            GuardStats {
+                guard,
+                shifts,
+                asleep,
+                most_slept,
+            }
+        })
+        .collect()
+}
+
+/// The puzzle's chart of each night, `#` for asleep.
+fn timeline(shifts: &[Shift]) -> String {
+    let tens: String = (0..60).map(|m| (b'0' + m / 10) as char).collect();
+    let units: String = (0..60).map(|m| (b'0' + m % 10) as char).collect();
+    let mut lines = vec![
+        "Date   ID      Minute".to_string(),
+        format!("               {}", tens),
+        format!("               {}", units),
+    ];
+    for shift in shifts {
+        let (_, month, day) = shift.date;
+        let chart: String = shift
+            .asleep
+            .iter()
+            .map(|&asleep| if asleep { '#' } else { '.' })
+            .collect();
+        let guard = format!("#{}", shift.guard);
+        lines.push(format!("{:02}-{:02}  {:<6}  {}", month, day, guard, chart));
+    }
+    lines.join("\n")
+}
+
+fn guards_text(stats: &[GuardStats]) -> String {
+    let mut lines = vec!["Guard   Shifts  Asleep  Minute  Times".to_string()];
+    for s in stats {
+        let (minute, times) = match s.most_slept {
+            Some((minute, times)) => (minute.to_string(), times.to_string()),
+            None => ("-".into(), "-".into()),
+        };
+        let guard = format!("#{}", s.guard);
+        lines.push(format!(
+            "{:<6} {:>7} {:>7} {:>7} {:>6}",
+            guard, s.shifts, s.asleep, minute, times
+        ));
+    }
+    lines.join("\n")
+}
+
+fn guards_csv(stats: &[GuardStats]) -> String {
+    let mut lines = vec!["guard,shifts,minutes_asleep,most_slept_minute,times".to_string()];
+    for s in stats {
+        let (minute, times) = s
+            .most_slept
+            .map_or((String::new(), String::new()), |(m, t)| {
+                (m.to_string(), t.to_string())
+            });
+        lines.push(format!(
+            "{},{},{},{},{}",
+            s.guard, s.shifts, s.asleep, minute, times
+        ));
+    }
+    lines.join("\n")
+}
+
+/// One row per night, with a 0/1 column for every minute of the midnight hour.
+fn shifts_csv(shifts: &[Shift]) -> String {
+    let minutes = (0..60).map(|m| format!("m{:02}", m)).join(",");
+    let mut lines = vec![format!("date,guard,minutes_asleep,{}", minutes)];
+    for shift in shifts {
+        let (year, month, day) = shift.date;
+        let asleep = shift.asleep.iter().filter(|&&a| a).count();
+        let chart = shift.asleep.iter().map(|&a| a as u8).join(",");
+        lines.push(format!(
+            "{}-{:02}-{:02},{},{},{}",
+            year, month, day, shift.guard, asleep, chart
+        ));
+    }
+    lines.join("\n")
+}
+
+fn report(reader: &mut dyn BufRead, params: &Params) -> Result<String> {
+    let lines = reader.lines().collect::<io::Result<Vec<_>>>()?;
+    let records = lines
+        .iter()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.parse())
+        .collect::<Result<_>>()?;
+    let shifts = shifts(records)?;
+    let format = params.get_str("format").unwrap_or("text");
+    // text shows both tables unless asked for one; CSV can only hold one
+    match (format, params.get_str("table")) {
+        ("text", None) => Ok(format!(
+            "{}\n\n{}",
+            guards_text(&guard_stats(&shifts)),
+            timeline(&shifts)
+        )),
+        ("text", Some("guards")) => Ok(guards_text(&guard_stats(&shifts))),
+        ("text", Some("shifts")) => Ok(timeline(&shifts)),
+        ("csv", None) | ("csv", Some("guards")) => Ok(guards_csv(&guard_stats(&shifts))),
+        ("csv", Some("shifts")) => Ok(shifts_csv(&shifts)),
+        ("text", _) | ("csv", _) => bail!("option `table` must be `guards` or `shifts`"),
+        _ => bail!("option `format` must be `text` or `csv`"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -233,4 +581,179 @@ mod tests {
         assert_eq!(res, 4455);
         Ok(())
     }
+
+    #[test]
+    fn test_shifts() -> Result<()> {
+        let shifts = shifts(parse_records(INPUT)?)?;
+        assert_eq!(shifts.len(), 5);
+        assert_eq!((shifts[1].guard, shifts[1].date), (99, (1518, 11, 2)));
+        let asleep: Vec<usize> = (0..60).filter(|&m| shifts[1].asleep[m]).collect();
+        assert_eq!(asleep, (40..50).collect::<Vec<_>>());
+        assert_eq!(next_day((1518, 12, 31)), (1519, 1, 1));
+        assert_eq!(next_day((1518, 2, 28)), (1518, 3, 1));
+        assert_eq!(next_day((1520, 2, 28)), (1520, 2, 29));
+        assert_eq!(next_day((1520, 2, 29)), (1520, 3, 1));
+        assert_eq!(next_day((1900, 2, 28)), (1900, 3, 1));
+        assert_eq!(next_day((2000, 2, 28)), (2000, 2, 29));
+        assert_eq!(date(365 + 365 + 59), (1520, 2, 29));
+        assert_eq!(date(365 + 365 + 366), (1521, 1, 1));
+        let leap = super::shifts(parse_records(
+            "[1520-02-28 23:58] Guard #10 begins shift\n[1520-02-29 00:05] falls asleep\n\
+             [1520-02-29 00:06] wakes up",
+        )?)?;
+        assert_eq!(leap[0].date, (1520, 2, 29));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_logs() {
+        let error = |log: &str| match parse_records(log).and_then(shifts) {
+            Ok(_) => panic!("accepted {}", log),
+            Err(err) => err.to_string(),
+        };
+        assert_eq!(
+            error("[1518-11-01 00:05] falls asleep"),
+            "[1518-11-01 00:05] record before any shift begins"
+        );
+        assert_eq!(
+            error("[1518-11-01 00:00] Guard #10 begins shift\n[1518-11-01 00:25] wakes up"),
+            "[1518-11-01 00:25] guard #10 wakes up without falling asleep"
+        );
+        assert_eq!(
+            error(
+                "[1518-11-01 00:00] Guard #10 begins shift\n[1518-11-01 00:05] falls asleep\n\
+                 [1518-11-01 23:58] Guard #99 begins shift"
+            ),
+            "[1518-11-01 23:58] guard #99 begins shift while guard #10 is still asleep \
+             (since 1518-11-01 00:05)"
+        );
+        assert_eq!(
+            error("[1518-11-01 23:58] Guard #99 begins shift\n[1518-11-01 23:59] falls asleep"),
+            "[1518-11-01 23:59] is outside guard #99's midnight hour on 1518-11-02"
+        );
+        assert_eq!(
+            error("[1518-11-01 00:00] Guard #10 begins shift\n[1518-11-01 00:05] falls asleep"),
+            "[1518-11-01 00:05] guard #10 falls asleep and never wakes up"
+        );
+        assert_eq!(
+            error("[1518-11-01 07:00] Guard #10 begins shift"),
+            "[1518-11-01 07:00] guard #10 begins shift after the midnight hour"
+        );
+        for record in &[
+            "[1518-00-10 00:00] Guard #10 begins shift",
+            "[1518-13-10 00:00] Guard #10 begins shift",
+            "[1518-11-00 00:00] Guard #10 begins shift",
+            "[1518-11-31 00:00] Guard #10 begins shift",
+            "[1518-02-29 00:00] Guard #10 begins shift",
+        ] {
+            assert_eq!(error(record), format!("not a valid date: `{}`", record));
+        }
+        assert!(parse_records("[1520-02-29 00:00] Guard #10 begins shift").is_ok());
+        for record in &["[1518-11-01 24:00] wakes up", "[1518-11-01 00:60] wakes up"] {
+            assert_eq!(error(record), format!("not a valid time: `{}`", record));
+        }
+        assert_eq!(
+            error("[1518-11-01 00:00] Guard #10 dozes off"),
+            "not a guard record: `[1518-11-01 00:00] Guard #10 dozes off`"
+        );
+    }
+
+    #[test]
+    fn test_guard_stats() -> Result<()> {
+        let shifts = shifts(parse_records(INPUT)?)?;
+        let mut log = shifts[..1].to_vec();
+        log.push(Shift::begin(
+            7,
+            &"[1518-12-01 00:00] Guard #7 begins shift".parse()?,
+        )?);
+        assert_eq!(
+            guard_stats(&log),
+            vec![
+                GuardStats {
+                    guard: 7,
+                    shifts: 1,
+                    asleep: 0,
+                    most_slept: None
+                },
+                GuardStats {
+                    guard: 10,
+                    shifts: 1,
+                    asleep: 45,
+                    most_slept: Some((5, 1))
+                }
+            ]
+        );
+        assert_eq!(
+            guard_stats(&shifts)[1],
+            GuardStats {
+                guard: 99,
+                shifts: 3,
+                asleep: 30,
+                most_slept: Some((45, 3))
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_report() -> Result<()> {
+        let params = Params::default();
+        let text = report(&mut INPUT.as_bytes(), &params)?;
+        assert!(text.starts_with(
+            "Guard   Shifts  Asleep  Minute  Times\n#10          2      50      24      2"
+        ));
+        assert!(text.contains(
+            "               000000000011111111112222222222333333333344444444445555555555\n\
+             \x20              012345678901234567890123456789012345678901234567890123456789\n\
+             11-01  #10     .....####################.....#########################.....\n\
+             11-02  #99     ........................................##########.........."
+        ));
+
+        let guards = report(
+            &mut INPUT.as_bytes(),
+            &Params::default().with("table", "guards"),
+        )?;
+        assert!(guards.starts_with("Guard   Shifts"));
+        assert!(!guards.contains("11-01  #10"));
+        let nights = report(
+            &mut INPUT.as_bytes(),
+            &Params::default().with("table", "shifts"),
+        )?;
+        assert!(nights.starts_with("Date   ID      Minute\n"));
+        assert!(!nights.contains("Guard"));
+        assert!(report(
+            &mut INPUT.as_bytes(),
+            &Params::default().with("table", "naps")
+        )
+        .is_err());
+
+        let csv = guards_csv(&guard_stats(&shifts(parse_records(INPUT)?)?));
+        assert_eq!(
+            csv,
+            "guard,shifts,minutes_asleep,most_slept_minute,times\n10,2,50,24,2\n99,3,30,45,3"
+        );
+        let rows = shifts_csv(&shifts(parse_records(INPUT)?)?);
+        assert!(rows
+            .lines()
+            .nth(2)
+            .expect("row")
+            .starts_with("1518-11-02,99,10,0,0,"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_round_trip() -> Result<()> {
+        let records = generate_records(&mut Rng::new(4), 100);
+        for record in records.iter() {
+            assert_eq!(&record.to_string().parse::<Record>()?, record);
+        }
+        assert_eq!(
+            records
+                .iter()
+                .filter(|r| matches!(r.action, Action::StartShift(_)))
+                .count(),
+            100
+        );
+        Ok(())
+    }
 }
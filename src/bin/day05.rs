@@ -1,112 +1,484 @@
-use aoc2018::{dispatch, Result};
-use itertools::Itertools;
-use std::collections::HashSet;
+use aoc2018::{Day, Params, Result, Rng};
+use failure::{bail, Error};
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::BufRead;
+use std::str::{self, FromStr};
 
 const DIFF: i8 = 'a' as i8 - 'A' as i8;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2)
+        .generator(&generate)
+        .mode("stream", &stream)
+        .mode("reactions", &reactions)
+        .dispatch()
 }
 
-fn reduce(input: Vec<char>) -> Vec<char> {
-    let mut res = vec![];
-    let mut skip = false;
-    for (a, b) in input.iter().tuple_windows() {
-        if skip {
-            skip = false;
-            continue;
+/// A polymer of `size` units, with reacting pairs planted so there is something to reduce.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    let mut polymer: Vec<char> = vec![];
+    while polymer.len() < size {
+        let unit = match polymer.last() {
+            Some(&last) if rng.one_in(3) => (last as i8 ^ DIFF) as u8 as char,
+            _ => {
+                let letter = (b'a' + rng.below(26) as u8) as char;
+                if rng.one_in(2) {
+                    letter.to_ascii_uppercase()
+                } else {
+                    letter
+                }
+            }
+        };
+        polymer.push(unit);
+    }
+    polymer.into_iter().collect()
+}
+
+/// Which pairs of units annihilate when they meet, in either order.
+///
+/// Pairs of ASCII units are looked up in a bit table, as hashing every unit is slow.
+#[derive(Debug, Clone, PartialEq)]
+struct ReactionRules {
+    ascii: [u128; 128],
+    pairs: HashSet<(char, char)>,
+}
+
+impl ReactionRules {
+    fn new() -> Self {
+        ReactionRules {
+            ascii: [0; 128],
+            pairs: HashSet::new(),
+        }
+    }
+
+    /// The puzzle's rules: the same ASCII letter in opposite cases.
+    fn opposite_case() -> Self {
+        let mut rules = ReactionRules::new();
+        for c in b'a'..=b'z' {
+            rules.add(c as char, c.to_ascii_uppercase() as char);
+        }
+        rules
+    }
+
+    fn add(&mut self, a: char, b: char) {
+        for &(a, b) in &[(a, b), (b, a)] {
+            if a.is_ascii() && b.is_ascii() {
+                self.ascii[a as usize] |= 1 << b as u32;
+            } else {
+                self.pairs.insert((a, b));
+            }
         }
-        if (*b as i8 - *a as i8).abs() == DIFF {
-            skip = true;
+    }
+
+    fn reacts(&self, a: char, b: char) -> bool {
+        if a.is_ascii() && b.is_ascii() {
+            self.ascii[a as usize] & (1 << b as u32) != 0
         } else {
-            res.push(*a);
+            self.pairs.contains(&(a, b))
         }
     }
-    if let Some(last) = input.last() {
-        if !skip {
-            res.push(*last);
+}
+
+/// One pair of units per line, like `a A`; blank lines and `#` comments are skipped.
+impl FromStr for ReactionRules {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut rules = ReactionRules::new();
+        for (n, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let units: Vec<&str> = line.split_whitespace().collect();
+            let (a, b) = match units[..] {
+                [a, b] if a.chars().count() == 1 && b.chars().count() == 1 => {
+                    (first_char(a), first_char(b))
+                }
+                _ => bail!("rules line {}: expected two units, found `{}`", n + 1, line),
+            };
+            rules.add(a, b);
         }
+        Ok(rules)
     }
-    res
 }
 
-fn full_reduce(chars: Vec<char>) -> usize {
-    let mut chars = chars;
-    let mut prev_len = chars.len();
-    loop {
-        chars = reduce(chars);
-        if chars.len() == prev_len {
-            break;
+fn first_char(s: &str) -> char {
+    s.chars().next().expect("one char")
+}
+
+/// Two units that annihilated, with their offsets in the input.
+#[derive(Debug, PartialEq)]
+struct Reaction {
+    left: (usize, char),
+    right: (usize, char),
+}
+
+impl fmt::Display for Reaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{} at {} and {}",
+            self.left.1, self.right.1, self.left.0, self.right.0
+        )
+    }
+}
+
+/// Reduces a polymer in one pass, however it arrives.
+///
+/// The units kept so far never react with each other, so each new unit can only react
+/// with the last one kept. Chunks can be split anywhere; whitespace is skipped. The kept
+/// units are held as text, a byte each for ASCII; only a logging reactor also keeps
+/// their offsets.
+struct Reactor<'r> {
+    rules: &'r ReactionRules,
+    kept: String,
+    count: usize,
+    fed: usize,
+    log: Option<Log>,
+}
+
+/// Where each kept unit came from, and every reaction so far.
+#[derive(Default)]
+struct Log {
+    offsets: Vec<usize>,
+    reactions: Vec<Reaction>,
+}
+
+impl<'r> Reactor<'r> {
+    fn new(rules: &'r ReactionRules) -> Self {
+        Reactor {
+            rules,
+            kept: String::new(),
+            count: 0,
+            fed: 0,
+            log: None,
+        }
+    }
+
+    /// Also records every reaction, which costs memory on big inputs.
+    fn logged(rules: &'r ReactionRules) -> Self {
+        let mut reactor = Reactor::new(rules);
+        reactor.log = Some(Log::default());
+        reactor
+    }
+
+    fn feed(&mut self, chunk: &str) {
+        for unit in chunk.chars() {
+            if !unit.is_whitespace() {
+                self.push(unit);
+            }
+            self.fed += 1;
         }
-        prev_len = chars.len();
     }
-    prev_len
+
+    fn push(&mut self, unit: char) {
+        let last = match self.kept.as_bytes().last() {
+            Some(&byte) if byte.is_ascii() => Some(char::from(byte)),
+            _ => self.kept.chars().next_back(),
+        };
+        match last {
+            Some(last) if self.rules.reacts(last, unit) => {
+                self.kept.pop();
+                self.count -= 1;
+                if let Some(log) = &mut self.log {
+                    let offset = log.offsets.pop().expect("an offset per kept unit");
+                    log.reactions.push(Reaction {
+                        left: (offset, last),
+                        right: (self.fed, unit),
+                    });
+                }
+            }
+            _ => {
+                self.kept.push(unit);
+                self.count += 1;
+                if let Some(log) = &mut self.log {
+                    log.offsets.push(self.fed);
+                }
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+
+    fn units(&self) -> &str {
+        &self.kept
+    }
+
+    fn reactions(&self) -> &[Reaction] {
+        self.log.as_ref().map_or(&[], |log| &log.reactions)
+    }
+}
+
+fn reduce(polymer: &str, rules: &ReactionRules) -> String {
+    let mut reactor = Reactor::new(rules);
+    reactor.feed(polymer);
+    reactor.kept
 }
 
 fn part1(input: &str) -> Result<usize> {
-    let chars: Vec<_> = input.chars().collect();
-    Ok(full_reduce(chars))
+    Ok(reduce(input, &ReactionRules::opposite_case())
+        .chars()
+        .count())
 }
 
+/// Taking out a unit type and then reducing gives the same as reducing first, so each
+/// letter is only taken out of the already reduced polymer.
 fn part2(input: &str) -> Result<usize> {
-    let chars: Vec<_> = input.chars().collect();
-    let mut letters = HashSet::new();
-    for c in input.chars() {
-        letters.insert(c.to_ascii_uppercase());
-    }
-    let mut shortest = input.len();
-    for &letter in letters.iter() {
-        let lower = letter.to_ascii_lowercase();
-        let without = chars
-            .iter()
-            .filter(|&&c| c != letter && c != lower)
-            .map(|&c| c)
-            .collect();
-        let len = full_reduce(without);
-        if len < shortest {
-            shortest = len;
+    let rules = ReactionRules::opposite_case();
+    let reduced = reduce(input, &rules);
+    let letters: BTreeSet<char> = reduced.chars().map(|u| u.to_ascii_lowercase()).collect();
+    Ok(letters
+        .into_iter()
+        .map(|letter| {
+            let mut reactor = Reactor::new(&rules);
+            for unit in reduced.chars() {
+                if unit.to_ascii_lowercase() != letter {
+                    reactor.push(unit);
+                }
+            }
+            reactor.len()
+        })
+        .min()
+        .unwrap_or(0))
+}
+
+fn read_rules(params: &Params) -> Result<ReactionRules> {
+    match params.get_str("rules") {
+        Some(path) => fs::read_to_string(path)?.parse(),
+        None => Ok(ReactionRules::opposite_case()),
+    }
+}
+
+/// Feeds the whole stream through the reactor in chunks, keeping any character split
+/// across two chunks for the next one.
+fn react_stream(reader: &mut dyn BufRead, reactor: &mut Reactor) -> Result<()> {
+    let mut pending: Vec<u8> = vec![];
+    let mut offset = 0;
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            break;
         }
+        pending.extend_from_slice(chunk);
+        let read = chunk.len();
+        reader.consume(read);
+        let valid = match str::from_utf8(&pending) {
+            Ok(text) => text.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(err) => bail!("invalid UTF-8 at byte {}", offset + err.valid_up_to()),
+        };
+        reactor.feed(str::from_utf8(&pending[..valid]).expect("checked"));
+        pending.drain(..valid);
+        offset += valid;
+    }
+    if !pending.is_empty() {
+        bail!("input ends partway through a character");
     }
-    // println!("{:?}", letters);
-    Ok(shortest)
+    Ok(())
+}
+
+/// Streams the polymer through the reactor, for inputs too big to read in whole.
+fn stream(reader: &mut dyn BufRead, params: &Params) -> Result<String> {
+    let rules = read_rules(params)?;
+    let mut reactor = Reactor::new(&rules);
+    react_stream(reader, &mut reactor)?;
+    Ok(reactor.len().to_string())
+}
+
+/// Every reaction in the order it happened, then what is left.
+fn reactions(reader: &mut dyn BufRead, params: &Params) -> Result<String> {
+    let rules = read_rules(params)?;
+    let mut reactor = Reactor::logged(&rules);
+    react_stream(reader, &mut reactor)?;
+    let mut lines: Vec<String> = reactor
+        .reactions()
+        .iter()
+        .map(|reaction| reaction.to_string())
+        .collect();
+    lines.push(format!(
+        "remaining: {} ({} units)",
+        reactor.units(),
+        reactor.len()
+    ));
+    Ok(lines.join("\n"))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aoc2018::difftest::DiffTest;
+    use itertools::Itertools;
 
     fn split(input: &str) -> Vec<char> {
         input.chars().collect()
     }
 
+    /// The original reduction, pass after pass until nothing changes.
+    fn reduce_pass(input: Vec<char>) -> Vec<char> {
+        let mut res = vec![];
+        let mut skip = false;
+        for (a, b) in input.iter().tuple_windows() {
+            if skip {
+                skip = false;
+                continue;
+            }
+            if (*b as i8 - *a as i8).abs() == DIFF {
+                skip = true;
+            } else {
+                res.push(*a);
+            }
+        }
+        if let Some(last) = input.last() {
+            if !skip {
+                res.push(*last);
+            }
+        }
+        res
+    }
+
+    fn full_reduce(chars: Vec<char>) -> usize {
+        let mut chars = chars;
+        let mut prev_len = chars.len();
+        loop {
+            chars = reduce_pass(chars);
+            if chars.len() == prev_len {
+                break;
+            }
+            prev_len = chars.len();
+        }
+        prev_len
+    }
+
+    fn part2_reference(input: &str) -> Result<usize> {
+        let chars: Vec<char> = input.chars().collect();
+        let letters: HashSet<char> = chars.iter().map(|c| c.to_ascii_lowercase()).collect();
+        Ok(letters
+            .into_iter()
+            .map(|letter| {
+                let without = chars.iter().filter(|c| c.to_ascii_lowercase() != letter);
+                full_reduce(without.cloned().collect())
+            })
+            .min()
+            .unwrap_or(0))
+    }
+
     #[test]
-    fn test_reduce1() {
-        assert_eq!(reduce(split("aA")), split(""));
+    fn test_reduce_pass1() {
+        assert_eq!(reduce_pass(split("aA")), split(""));
     }
 
     #[test]
-    fn test_reduce2() {
-        assert_eq!(reduce(split("abBA")), split("aA"));
+    fn test_reduce_pass2() {
+        assert_eq!(reduce_pass(split("abBA")), split("aA"));
     }
 
     #[test]
-    fn test_reduce3() {
-        assert_eq!(reduce(split("abAB")), split("abAB"));
+    fn test_reduce_pass3() {
+        assert_eq!(reduce_pass(split("abAB")), split("abAB"));
     }
 
     #[test]
-    fn test_reduce4() {
-        assert_eq!(reduce(split("aAab")), split("ab"));
+    fn test_reduce_pass4() {
+        assert_eq!(reduce_pass(split("aAab")), split("ab"));
     }
 
     #[test]
-    fn test_reduce5() {
+    fn test_reduce_pass5() {
         let v = split("dabAcCaCBAcCcaDA");
-        let v = reduce(v);
-        let v = reduce(v);
-        let v = reduce(v);
+        let v = reduce_pass(v);
+        let v = reduce_pass(v);
+        let v = reduce_pass(v);
         assert_eq!(v, split("dabCBAcaDA"));
-        let v = reduce(v);
+        let v = reduce_pass(v);
         assert_eq!(v, split("dabCBAcaDA"));
     }
+
+    const INPUT: &str = "dabAcCaCBAcCcaDA";
+
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(reduce(INPUT, &ReactionRules::opposite_case()), "dabCBAcaDA");
+        Ok(assert_eq!(part1(INPUT)?, 10))
+    }
+
+    #[test]
+    fn test_part2() -> Result<()> {
+        Ok(assert_eq!(part2(INPUT)?, 4))
+    }
+
+    #[test]
+    fn test_stream() -> Result<()> {
+        let rules = ReactionRules::opposite_case();
+        let mut reactor = Reactor::new(&rules);
+        for chunk in ["dab", "AcC", "aC\nB", "AcCcaDA"].iter() {
+            reactor.feed(chunk);
+        }
+        assert_eq!(reactor.units(), "dabCBAcaDA");
+        // a byte per ASCII unit, and no offsets without a log
+        assert_eq!((reactor.kept.len(), reactor.len()), (10, 10));
+        assert!(reactor.log.is_none());
+        let mut reader = std::io::BufReader::with_capacity(4, "dabAc\nCaCBAcCcaDA\n".as_bytes());
+        assert_eq!(stream(&mut reader, &Params::default())?, "10");
+        Ok(())
+    }
+
+    #[test]
+    fn test_rules() -> Result<()> {
+        let rules: ReactionRules = "# Greek\nα Α\nβ Β\n\n+ -  # charges".parse()?;
+        assert!(rules.reacts('Α', 'α') && rules.reacts('-', '+'));
+        assert!(!rules.reacts('a', 'A'));
+        assert_eq!(reduce("αβΒγ+-Αα", &rules), "αγ");
+        assert_eq!(
+            "a A\nab B"
+                .parse::<ReactionRules>()
+                .unwrap_err()
+                .to_string(),
+            "rules line 2: expected two units, found `ab B`"
+        );
+
+        // the stream splits the two-byte letters across chunks
+        let mut reactor = Reactor::logged(&rules);
+        let mut reader = std::io::BufReader::with_capacity(3, "αβΒγ\n+-Αα".as_bytes());
+        react_stream(&mut reader, &mut reactor)?;
+        let log: Vec<String> = reactor.reactions().iter().map(|r| r.to_string()).collect();
+        assert_eq!(log, vec!["βΒ at 1 and 2", "+- at 5 and 6", "Αα at 7 and 8"]);
+        let mut bad = std::io::BufReader::new(&b"ab\xff"[..]);
+        assert!(react_stream(&mut bad, &mut Reactor::new(&rules)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reactions() -> Result<()> {
+        let log = reactions(&mut "dabAcCaCBAcCcaDA".as_bytes(), &Params::default())?;
+        assert_eq!(
+            log,
+            "cC at 4 and 5\nAa at 3 and 6\ncC at 10 and 11\nremaining: dabCBAcaDA (10 units)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fast_matches_reference() {
+        let reference = |input: &str| Ok(full_reduce(input.chars().collect()));
+        DiffTest::new(&generate, &part1, &reference)
+            .sizes(0..=60)
+            .assert_agree();
+        DiffTest::new(&generate, &part2, &part2_reference)
+            .sizes(0..=60)
+            .assert_agree();
+    }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let input = generate(&mut Rng::new(5), 200);
+        assert_eq!(input.len(), 200);
+        assert!(input.chars().all(|c| c.is_ascii_alphabetic()));
+        assert!(part1(&input)? < 200);
+        Ok(())
+    }
 }
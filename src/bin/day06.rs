@@ -1,25 +1,32 @@
-use aoc2018::{dispatch, Result};
-use failure::{err_msg, Error};
+use aoc2018::{Day, Params, Result, Rng};
+use failure::{bail, err_msg, format_err, Error};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::{Captures, Regex};
-use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
 use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 struct Point {
     x: i32,
     y: i32,
+    z: i32,
 }
 
+/// A point, and whether it was given with a z coordinate.
 impl FromStr for Point {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
         lazy_static! {
-            static ref RE: Regex = Regex::new(r"(?P<x>\d+), (?P<y>\d+)").unwrap();
+            static ref RE: Regex =
+                Regex::new(r"^(?P<x>-?\d+), (?P<y>-?\d+)(, (?P<z>-?\d+))?$").unwrap();
         }
 
-        let caps = RE.captures(s).unwrap();
+        let caps = RE
+            .captures(s)
+            .ok_or_else(|| format_err!("not a coordinate: `{}`", s))?;
         fn get_cap_int(caps: &Captures, name: &str) -> Result<i32> {
             Ok(caps
                 .name(name)
@@ -27,152 +34,670 @@ impl FromStr for Point {
                 .as_str()
                 .parse()?)
         }
-        Ok(Point::new(
+        let z = match caps.name("z") {
+            Some(_) => get_cap_int(&caps, "z")?,
+            None => 0,
+        };
+        Ok(Point::at(
             get_cap_int(&caps, "x")?,
             get_cap_int(&caps, "y")?,
+            z,
         ))
     }
 }
 
+/// `x, y`, with `, z` as well if z isn't 0 or the alternate flag asks for 3 dimensions.
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, {}", self.x, self.y)?;
+        if self.z != 0 || f.alternate() {
+            write!(f, ", {}", self.z)?;
+        }
+        Ok(())
+    }
+}
+
 impl Point {
     fn new(x: i32, y: i32) -> Self {
-        Point { x, y }
+        Point::at(x, y, 0)
     }
-}
 
-fn main() {
-    dispatch(&part1, &part2)
+    fn at(x: i32, y: i32, z: i32) -> Self {
+        Point { x, y, z }
+    }
+
+    fn random(rng: &mut Rng, max: i32) -> Self {
+        Point::new(
+            rng.range(0, max.into()) as i32,
+            rng.range(0, max.into()) as i32,
+        )
+    }
+
+    fn minus(self, other: Point) -> [i64; 3] {
+        [
+            i64::from(self.x - other.x),
+            i64::from(self.y - other.y),
+            i64::from(self.z - other.z),
+        ]
+    }
+
+    fn offsets(self, other: Point) -> [i64; 3] {
+        [
+            i64::from(self.x - other.x).abs(),
+            i64::from(self.y - other.y).abs(),
+            i64::from(self.z - other.z).abs(),
+        ]
+    }
 }
 
-#[derive(Debug)]
-enum Distance {
-    Unset,
-    Equal(i32),
-    // distance, point #
-    Best(i32, usize),
+/// `size` coordinates over a square growing with `size`.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    let max = 10 + 4 * size as i32;
+    let points: Vec<Point> = (0..size.max(1)).map(|_| Point::random(rng, max)).collect();
+    format_points(&points, 2)
 }
 
-fn parse_distances(input: &str) -> (HashMap<i32, Distance>, Point) {
-    use self::Distance::*;
+fn main() {
+    Day::new(&part1, &part2)
+        .generator(&generate)
+        .mode("regions", &regions)
+        .mode("render", &render)
+        .dispatch()
+}
 
-    let mut distances = HashMap::new();
+/// The points, and whether they are in 2 or 3 dimensions.
+fn parse_points(input: &str) -> Result<(Vec<Point>, usize)> {
     let points: Vec<Point> = input
         .split('\n')
-        .filter_map(|row| row.parse().ok())
+        .map(|row| row.parse())
+        .collect::<Result<_>>()?;
+    if points.is_empty() {
+        bail!("no coordinates");
+    }
+    let dims: Vec<usize> = input
+        .split('\n')
+        .map(|row| row.matches(',').count() + 1)
+        .dedup()
         .collect();
-    // println!("{:?}", points);
-    let maxx = points.iter().map(|p| p.x).max().unwrap() + 1;
-    let maxy = points.iter().map(|p| p.y).max().unwrap() + 1;
-    for (pid, point) in points.iter().enumerate() {
-        for x in 0..maxx {
-            for y in 0..maxy {
-                let distance = distances.entry(x + maxx * y).or_insert(Unset);
-                let point_distance = (point.x - x).abs() + (point.y - y).abs();
-                // println!("{:?}, {}", *distance, point_distance);
-                *distance = match *distance {
-                    Unset => Best(point_distance, pid),
-                    Best(d, _) if d == point_distance => Equal(d),
-                    Best(d, _) if d > point_distance => Best(point_distance, pid),
-                    Best(d, i) if d < point_distance => Best(d, i),
-                    Equal(d) if d <= point_distance => Equal(d),
-                    Equal(d) if d > point_distance => Best(point_distance, pid),
-                    _ => unreachable!(),
-                }
+    match dims[..] {
+        [dims] => Ok((points, dims)),
+        _ => bail!("coordinates mix 2 and 3 dimensions"),
+    }
+}
+
+/// The points a line each, as `parse_points` reads them back.
+fn format_points(points: &[Point], dims: usize) -> String {
+    points
+        .iter()
+        .map(|p| {
+            if dims == 3 {
+                format!("{:#}", p)
+            } else {
+                p.to_string()
             }
+        })
+        .join("\n")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Metric {
+    Manhattan,
+    Chebyshev,
+    EuclideanSquared,
+}
+
+impl Metric {
+    fn distance(self, a: Point, b: Point) -> i64 {
+        let [x, y, z] = a.offsets(b);
+        match self {
+            Metric::Manhattan => x + y + z,
+            Metric::Chebyshev => x.max(y).max(z),
+            Metric::EuclideanSquared => x * x + y * y + z * z,
         }
     }
-    (distances, Point::new(maxx, maxy))
 }
 
-fn _print(distances: &HashMap<i32, Distance>, max: &Point) {
-    use self::Distance::*;
-    let maxx = max.x;
-    let maxy = max.y;
-    // println!("{:?}", distances);
-    for y in 0..maxy {
-        for x in 0..maxx {
-            let c = match distances.get(&(x + maxx * y)) {
-                Some(Unset) => 'Q',
-                Some(Best(0, i)) => ('A' as u8 + *i as u8) as char,
-                Some(Best(_, i)) => ('a' as u8 + *i as u8) as char,
-                Some(Equal(_)) => '.',
-                None => unreachable!(),
-            };
-            print!("{}", c);
+impl FromStr for Metric {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "manhattan" => Ok(Metric::Manhattan),
+            "chebyshev" => Ok(Metric::Chebyshev),
+            "euclidean2" => Ok(Metric::EuclideanSquared),
+            _ => bail!(
+                "expected `manhattan`, `chebyshev` or `euclidean2`, found `{}`",
+                s
+            ),
         }
-        println!("");
     }
 }
 
-#[derive(Debug)]
-enum Region {
-    Infinite,
-    Finite(u32),
+/// The smallest box holding every point, corners included. In 2 dimensions z stays 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Bounds {
+    min: Point,
+    max: Point,
+    dims: usize,
 }
 
-fn part1(input: &str) -> Result<u32> {
-    use self::Distance::*;
-    use self::Region::*;
-    let mut regions = HashMap::new();
-    let (distances, max) = parse_distances(input);
-    let maxx = max.x;
-    let maxy = max.y;
-    for y in 0..maxy {
-        for x in 0..maxx {
-            if let Some(Best(_, i)) = distances.get(&(x + maxx * y)) {
-                let new = match regions.get(i) {
-                    None => Finite(1),
-                    Some(Infinite) => Infinite,
-                    Some(Finite(count)) => Finite(count + 1),
-                };
-                if x == 0 || x == maxx - 1 || y == 0 || y == maxy - 1 {
-                    regions.insert(i, Infinite);
-                } else {
-                    regions.insert(i, new);
-                }
-            };
+impl Bounds {
+    fn of(points: &[Point], dims: usize) -> Self {
+        let min = |axis: fn(&Point) -> i32| points.iter().map(axis).min().unwrap_or(0);
+        let max = |axis: fn(&Point) -> i32| points.iter().map(axis).max().unwrap_or(0);
+        Bounds {
+            min: Point::at(min(|p| p.x), min(|p| p.y), min(|p| p.z)),
+            max: Point::at(max(|p| p.x), max(|p| p.y), max(|p| p.z)),
+            dims,
         }
     }
-    // _print(&distances, &max);
-    // println!("{:?}", regions);
-    Ok(*regions
-        .values()
-        .filter_map(|r| if let Finite(c) = r { Some(c) } else { None })
-        .max()
-        .unwrap())
+
+    fn grow(&self, margin: i32) -> Self {
+        let z_margin = if self.dims == 3 { margin } else { 0 };
+        Bounds {
+            min: Point::at(
+                self.min.x - margin,
+                self.min.y - margin,
+                self.min.z - z_margin,
+            ),
+            max: Point::at(
+                self.max.x + margin,
+                self.max.y + margin,
+                self.max.z + z_margin,
+            ),
+            dims: self.dims,
+        }
+    }
+
+    fn width(&self) -> usize {
+        (self.max.x - self.min.x + 1) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.max.y - self.min.y + 1) as usize
+    }
+
+    /// The longest side, less one.
+    fn span(&self) -> i32 {
+        (self.max.x - self.min.x)
+            .max(self.max.y - self.min.y)
+            .max(self.max.z - self.min.z)
+    }
+
+    /// Every cell, row by row and then layer by layer.
+    fn cells(&self) -> impl Iterator<Item = Point> {
+        let (min, max) = (self.min, self.max);
+        (min.z..=max.z).flat_map(move |z| {
+            (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| Point::at(x, y, z)))
+        })
+    }
+
+    fn on_edge(&self, p: Point) -> bool {
+        p.x == self.min.x
+            || p.x == self.max.x
+            || p.y == self.min.y
+            || p.y == self.max.y
+            || (self.dims == 3 && (p.z == self.min.z || p.z == self.max.z))
+    }
 }
 
-fn area(input: &str, max_distance: i32) -> i32 {
-    let points: Vec<Point> = input
-        .split('\n')
-        .filter_map(|row| row.parse().ok())
+/// The closest point to `cell`, or `None` if two or more are equally close.
+fn closest(points: &[Point], metric: Metric, cell: Point) -> Option<usize> {
+    let mut best = None;
+    let mut best_distance = i64::MAX;
+    for (i, &point) in points.iter().enumerate() {
+        let distance = metric.distance(cell, point);
+        if distance < best_distance {
+            best = Some(i);
+            best_distance = distance;
+        } else if distance == best_distance {
+            best = None;
+        }
+    }
+    best
+}
+
+/// Whether the Euclidean region of `points[i]` is infinite, i.e. it is on the convex hull.
+fn on_hull(points: &[Point], i: usize, dims: usize) -> bool {
+    let offsets: Vec<[i64; 3]> = points
+        .iter()
+        .enumerate()
+        .filter(|&(j, _)| j != i)
+        .map(|(_, &q)| q.minus(points[i]))
+        .collect();
+    // a duplicated point owns nothing
+    if offsets.contains(&[0, 0, 0]) {
+        return false;
+    }
+    if offsets.is_empty() {
+        return true;
+    }
+    // a line (or plane) with every other point on one side can be turned until it
+    // meets another point, or two in 3 dimensions, so only those normals need trying
+    let axes = if dims == 3 { 0..3 } else { 2..3 };
+    let mut normals = vec![];
+    for a in &offsets {
+        for axis in axes.clone() {
+            let mut unit = [0; 3];
+            unit[axis] = 1;
+            normals.push(cross(*a, unit));
+        }
+        if dims == 3 {
+            normals.extend(offsets.iter().map(|&b| cross(*a, b)));
+        }
+    }
+    let dot = |a: [i64; 3], b: [i64; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+    normals.into_iter().filter(|&n| n != [0, 0, 0]).any(|n| {
+        let sides = offsets.iter().map(|&a| dot(a, n).signum());
+        sides.clone().all(|side| side <= 0) || sides.clone().all(|side| side >= 0)
+    })
+}
+
+/// A box round the finite Euclidean region of `points[i]`, found from its corners.
+fn cell_bounds(points: &[Point], i: usize, dims: usize) -> Bounds {
+    let offsets: Vec<[i128; 3]> = points
+        .iter()
+        .map(|&q| {
+            let [x, y, z] = q.minus(points[i]);
+            [i128::from(x), i128::from(y), i128::from(z)]
+        })
+        .filter(|&a| a != [0, 0, 0])
         .collect();
+    let dot = |a: [i128; 3], b: [i128; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+    let det = |m: [[i128; 3]; 3]| {
+        dot(
+            m[0],
+            [
+                m[1][1] * m[2][2] - m[1][2] * m[2][1],
+                m[1][2] * m[2][0] - m[1][0] * m[2][2],
+                m[1][0] * m[2][1] - m[1][1] * m[2][0],
+            ],
+        )
+    };
+    let (mut low, mut high) = ([0; 3], [0; 3]);
+    for rows in offsets.iter().combinations(dims) {
+        // a corner is as far from the point as from `dims` others: equally far from the
+        // origin and from `a` means 2 a.u = a.a; in 2 dimensions u_z = 0 is the third
+        let mut matrix = [[0, 0, 1]; 3];
+        let mut targets = [0; 3];
+        for (k, &&a) in rows.iter().enumerate() {
+            matrix[k] = a;
+            targets[k] = dot(a, a);
+        }
+        let d = 2 * det(matrix);
+        if d == 0 {
+            continue;
+        }
+        let mut corner = [0; 3];
+        for (axis, c) in corner.iter_mut().enumerate() {
+            let mut replaced = matrix;
+            for (row, &t) in replaced.iter_mut().zip(&targets) {
+                row[axis] = t;
+            }
+            *c = det(replaced);
+        }
+        // the corner is `corner / d`
+        let (corner, d) = if d < 0 {
+            (corner.map(|c| -c), -d)
+        } else {
+            (corner, d)
+        };
+        // and no nearer any of the rest
+        if offsets.iter().all(|&a| 2 * dot(corner, a) <= dot(a, a) * d) {
+            for axis in 0..3 {
+                low[axis] = low[axis].min(corner[axis].div_euclid(d));
+                high[axis] = high[axis].max(-(-corner[axis]).div_euclid(d));
+            }
+        }
+    }
+    let at = |offset: [i128; 3]| {
+        let p = points[i];
+        let clamp = |c: i128| c.clamp(i32::MIN.into(), i32::MAX.into()) as i32;
+        Point::at(
+            clamp(i128::from(p.x) + offset[0]),
+            clamp(i128::from(p.y) + offset[1]),
+            clamp(i128::from(p.z) + offset[2]),
+        )
+    };
+    Bounds {
+        min: at(low),
+        max: at(high),
+        dims,
+    }
+}
+
+fn cross(a: [i64; 3], b: [i64; 3]) -> [i64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
 
-    let mut area = 0;
-    let maxx = points.iter().map(|p| p.x).max().unwrap() + 1;
-    let maxy = points.iter().map(|p| p.y).max().unwrap() + 1;
-    for x in 0..maxx {
-        for y in 0..maxy {
-            let total_distance: i32 = points
-                .iter()
-                .map(|point| (point.x - x).abs() + (point.y - y).abs())
-                .sum();
-
-            if total_distance < max_distance {
-                area += 1;
+/// The closest point to every cell in the bounding box.
+struct Voronoi {
+    points: Vec<Point>,
+    metric: Metric,
+    bounds: Bounds,
+    owners: Vec<Option<usize>>,
+}
+
+impl Voronoi {
+    fn new(points: Vec<Point>, dims: usize, metric: Metric) -> Self {
+        let bounds = Bounds::of(&points, dims);
+        let owners = bounds
+            .cells()
+            .map(|cell| closest(&points, metric, cell))
+            .collect();
+        Voronoi {
+            points,
+            metric,
+            bounds,
+            owners,
+        }
+    }
+
+    fn owner(&self, cell: Point) -> Option<usize> {
+        let x = (cell.x - self.bounds.min.x) as usize;
+        let y = (cell.y - self.bounds.min.y) as usize;
+        let z = (cell.z - self.bounds.min.z) as usize;
+        self.owners[x + self.bounds.width() * (y + self.bounds.height() * z)]
+    }
+
+    /// Which points' regions go on forever.
+    fn infinite(&self) -> Vec<bool> {
+        let mut infinite = vec![false; self.points.len()];
+        let edge: Vec<Option<usize>> = match self.metric {
+            // a cell outside the box has the owner of the nearest cell on its edge
+            Metric::Manhattan => self
+                .bounds
+                .cells()
+                .filter(|&cell| self.bounds.on_edge(cell))
+                .map(|cell| self.owner(cell))
+                .collect(),
+            Metric::Chebyshev => {
+                // past the span, stepping out adds 1 to every distance that counts, so
+                // the owner stays the same all the way out
+                let grown = self.bounds.grow(self.bounds.span() + 1);
+                grown
+                    .cells()
+                    .filter(|&cell| grown.on_edge(cell))
+                    .map(|cell| closest(&self.points, self.metric, cell))
+                    .collect()
+            }
+            Metric::EuclideanSquared => (0..self.points.len())
+                .map(|i| Some(i).filter(|&i| on_hull(&self.points, i, self.bounds.dims)))
+                .collect(),
+        };
+        for i in Iterator::flatten(edge.into_iter()) {
+            infinite[i] = true;
+        }
+        infinite
+    }
+
+    /// Each point's area, or `None` if it goes on forever.
+    fn areas(&self) -> Vec<Option<u32>> {
+        let infinite = self.infinite();
+        let mut areas = vec![0; self.points.len()];
+        match self.metric {
+            Metric::Manhattan => {
+                for i in Iterator::flatten(self.owners.iter()) {
+                    areas[*i] += 1;
+                }
+            }
+            // finite regions stay in the box, or within the span of it for Chebyshev
+            Metric::Chebyshev => {
+                for cell in self.bounds.grow(self.bounds.span()).cells() {
+                    if let Some(i) = closest(&self.points, self.metric, cell) {
+                        areas[i] += 1;
+                    }
+                }
+            }
+            // near the hull a finite region can reach far out, so each gets its own box
+            Metric::EuclideanSquared => {
+                for i in (0..self.points.len()).filter(|&i| !infinite[i]) {
+                    areas[i] = cell_bounds(&self.points, i, self.bounds.dims)
+                        .cells()
+                        .filter(|&cell| closest(&self.points, self.metric, cell) == Some(i))
+                        .count() as u32;
+                }
             }
         }
+        areas
+            .into_iter()
+            .zip(infinite)
+            .map(|(area, infinite)| if infinite { None } else { Some(area) })
+            .collect()
+    }
+
+    /// The layer at `z` as a plain PPM image, with infinite regions dimmed.
+    fn to_ppm(&self, z: i32) -> String {
+        let infinite = self.infinite();
+        let (min, max) = (self.bounds.min, self.bounds.max);
+        let mut lines = vec![format!(
+            "P3\n{} {}\n255",
+            self.bounds.width(),
+            self.bounds.height()
+        )];
+        for y in min.y..=max.y {
+            let mut row = (min.x..=max.x).map(|x| {
+                let cell = Point::at(x, y, z);
+                let (r, g, b) = match self.owner(cell) {
+                    Some(i) if self.points[i] == cell => (0, 0, 0),
+                    Some(i) => colour(i, infinite[i]),
+                    None => (200, 200, 200),
+                };
+                format!("{} {} {}", r, g, b)
+            });
+            lines.push(row.join(" "));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Well spread hues, by stepping round the colour wheel by the golden ratio.
+fn colour(i: usize, dim: bool) -> (u8, u8, u8) {
+    let hue = (i as f64 * 0.618_034).fract() * 6.0;
+    let value = if dim { 0.5 } else { 0.95 };
+    let (low, high) = (value * 0.35, value);
+    let rise = low + (high - low) * hue.fract();
+    let fall = high - (high - low) * hue.fract();
+    let (r, g, b) = match hue as u8 {
+        0 => (high, rise, low),
+        1 => (fall, high, low),
+        2 => (low, high, rise),
+        3 => (low, fall, high),
+        4 => (rise, low, high),
+        _ => (high, low, fall),
+    };
+    let byte = |c: f64| (c * 255.0).round() as u8;
+    (byte(r), byte(g), byte(b))
+}
+
+fn largest_finite(voronoi: &Voronoi) -> Result<u32> {
+    Iterator::flatten(voronoi.areas().into_iter())
+        .max()
+        .ok_or_else(|| err_msg("every region is infinite"))
+}
+
+fn part1(input: &str) -> Result<u32> {
+    let (points, dims) = parse_points(input)?;
+    largest_finite(&Voronoi::new(points, dims, Metric::Manhattan))
+}
+
+fn area(points: &[Point], dims: usize, metric: Metric, max_distance: i64) -> usize {
+    match metric {
+        Metric::Manhattan => separable_area(points, dims, max_distance),
+        _ => scan_area(points, dims, metric, max_distance),
+    }
+}
+
+/// Counts the close enough cells one by one.
+fn scan_area(points: &[Point], dims: usize, metric: Metric, max_distance: i64) -> usize {
+    // a cell `d` outside the box is at least `d` from every point in any metric
+    let margin = (max_distance - 1).max(0) / points.len() as i64;
+    Bounds::of(points, dims)
+        .grow(margin as i32)
+        .cells()
+        .filter(|&cell| {
+            let total: i64 = points.iter().map(|&p| metric.distance(cell, p)).sum();
+            total < max_distance
+        })
+        .count()
+}
+
+/// The sorted totals below `limit` of the distances along one axis to all the `coords`.
+fn axis_totals(mut coords: Vec<i64>, limit: i64) -> Vec<i64> {
+    coords.sort();
+    let n = coords.len() as i64;
+    let (first, last) = (coords[0], coords[coords.len() - 1]);
+    let from_first: i64 = coords.iter().map(|c| c - first).sum();
+    let from_last: i64 = coords.iter().map(|c| last - c).sum();
+    // outside the coordinates each step away adds one per coordinate
+    let reach = |total: i64| {
+        if total < limit {
+            (limit - 1 - total) / n
+        } else {
+            0
+        }
+    };
+
+    let start = first - reach(from_first);
+    let end = last + reach(from_last);
+    let mut total = from_first + n * (first - start);
+    let mut behind = 0;
+    let mut totals = vec![];
+    for x in start..=end {
+        if total < limit {
+            totals.push(total);
+        }
+        // each step adds the coordinates left behind and takes away the ones ahead
+        while behind < coords.len() && coords[behind] <= x {
+            behind += 1;
+        }
+        total += 2 * behind as i64 - n;
+    }
+    totals.sort();
+    totals
+}
+
+/// How many combinations of one total per axis add up to less than `budget`.
+fn count_under(axes: &[Vec<i64>], budget: i64) -> usize {
+    match axes {
+        [] => 0,
+        [last] => last.partition_point(|&total| total < budget),
+        [first, rest @ ..] => first
+            .iter()
+            .take_while(|&&total| total < budget)
+            .map(|&total| count_under(rest, budget - total))
+            .sum(),
     }
-    area
 }
 
-fn part2(input: &str) -> Result<i32> {
-    Ok(area(input, 10000))
+/// Counts the cells by adding up per-axis totals, as Manhattan distance allows.
+fn separable_area(points: &[Point], dims: usize, max_distance: i64) -> usize {
+    let axes: Vec<Vec<i64>> = [|p: &Point| p.x, |p: &Point| p.y, |p: &Point| p.z][..dims]
+        .iter()
+        .map(|axis| points.iter().map(|p| i64::from(axis(p))).collect())
+        .collect();
+    // each axis can only use what the others leave at their smallest
+    let smallest: Vec<i64> = axes
+        .iter()
+        .map(|coords| {
+            let median = coords.iter().sorted()[coords.len() / 2];
+            coords.iter().map(|c| (c - median).abs()).sum()
+        })
+        .collect();
+    let least: i64 = smallest.iter().sum();
+    let totals: Vec<Vec<i64>> = axes
+        .into_iter()
+        .zip(&smallest)
+        .map(|(coords, smallest)| axis_totals(coords, max_distance - (least - smallest)))
+        .collect();
+    count_under(&totals, max_distance)
+}
+
+fn part2(input: &str) -> Result<usize> {
+    let (points, dims) = parse_points(input)?;
+    Ok(area(&points, dims, Metric::Manhattan, 10000))
+}
+
+fn read_points(reader: &mut dyn BufRead) -> Result<(Vec<Point>, usize)> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    parse_points(input.trim())
+}
+
+fn regions(reader: &mut dyn BufRead, params: &Params) -> Result<String> {
+    let metric: Metric = params.get("metric", Metric::Manhattan)?;
+    let threshold = params.get("threshold", 10000)?;
+    let (points, dims) = read_points(reader)?;
+    let safe = area(&points, dims, metric, threshold);
+    let largest = largest_finite(&Voronoi::new(points, dims, metric))
+        .map_or_else(|_| "none".to_string(), |area| area.to_string());
+    Ok(format!(
+        "largest finite region: {}\nsafe region: {}",
+        largest, safe
+    ))
+}
+
+fn render(reader: &mut dyn BufRead, params: &Params) -> Result<String> {
+    let metric = params.get("metric", Metric::Manhattan)?;
+    let (points, dims) = read_points(reader)?;
+    let voronoi = Voronoi::new(points, dims, metric);
+    let (min, max) = (voronoi.bounds.min.z, voronoi.bounds.max.z);
+    let z = params.get("z", min)?;
+    if z < min || z > max {
+        bail!("option `z` must be between {} and {}", min, max);
+    }
+    Ok(voronoi.to_ppm(z))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aoc2018::difftest::DiffTest;
+
+    const INPUT: &str = "1, 1
+1, 6
+8, 3
+3, 4
+5, 5
+8, 9";
+
+    #[test]
+    fn test_areas() -> Result<()> {
+        let (points, dims) = parse_points(INPUT)?;
+        let voronoi = Voronoi::new(points, dims, Metric::Manhattan);
+        assert_eq!(
+            voronoi.areas(),
+            vec![None, None, None, Some(9), Some(17), None]
+        );
+        assert_eq!(voronoi.owner(Point::new(5, 2)), Some(4));
+        assert_eq!(voronoi.owner(Point::new(1, 4)), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_negative_coordinates() -> Result<()> {
+        let shifted: Vec<Point> = parse_points(INPUT)?
+            .0
+            .iter()
+            .map(|p| Point::new(p.x - 100, p.y - 50))
+            .collect();
+        let shifted = format_points(&shifted, 2);
+        assert_eq!(part1(&shifted)?, 17);
+        let (points, dims) = parse_points(&shifted)?;
+        assert_eq!(area(&points, dims, Metric::Manhattan, 32), 16);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_input() {
+        assert!(part1("1, 1\nx, 2").is_err());
+        assert!(part1("1, 1").is_err());
+        assert!(part1("1, 1\n2, 2, 2").is_err());
+    }
 
     #[test]
     fn test_part1() -> Result<()> {
@@ -190,18 +715,244 @@ mod tests {
     }
 
     #[test]
-    fn test_part2() {
+    fn test_part2() -> Result<()> {
+        let (points, dims) = parse_points(INPUT)?;
+        Ok(assert_eq!(area(&points, dims, Metric::Manhattan, 32), 16))
+    }
+
+    #[test]
+    fn test_area_beyond_bounds() {
+        let points = vec![Point::new(0, 0), Point::new(2, 0)];
+        // row 0 runs from -4 to 6, and each row further out is two cells shorter
         assert_eq!(
-            area(
-                "1, 1
-1, 6
-8, 3
-3, 4
-5, 5
-8, 9",
-                32
-            ),
-            16
-        )
+            area(&points, 2, Metric::Manhattan, 11),
+            3 + 5 + 7 + 9 + 11 + 9 + 7 + 5 + 3
+        );
+    }
+
+    #[test]
+    fn test_metrics() -> Result<()> {
+        let (a, b) = (Point::at(1, -2, 3), Point::at(4, 2, 3));
+        assert_eq!(Metric::Manhattan.distance(a, b), 7);
+        assert_eq!(Metric::Chebyshev.distance(a, b), 4);
+        assert_eq!(Metric::EuclideanSquared.distance(a, b), 25);
+        assert!("taxicab".parse::<Metric>().is_err());
+
+        let (points, dims) = parse_points(INPUT)?;
+        assert_eq!(area(&points, dims, Metric::Chebyshev, 20), 10);
+        let voronoi = Voronoi::new(points, dims, Metric::EuclideanSquared);
+        assert_eq!(
+            voronoi.infinite(),
+            vec![true, true, true, false, false, true]
+        );
+        assert_eq!(voronoi.owner(Point::new(4, 4)), Some(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_interior_point() -> Result<()> {
+        // 20, 20 is just inside the hull of the others, so its region is finite, though
+        // it runs out as far as -14, 459
+        let input = "3, 19\n23, 9\n31, 21\n29, 15\n20, 20\n11, 4";
+        let (points, dims) = parse_points(input)?;
+        let voronoi = Voronoi::new(points, dims, Metric::EuclideanSquared);
+        assert_eq!(
+            voronoi.infinite(),
+            vec![true, true, true, true, false, true]
+        );
+        let cell = cell_bounds(&voronoi.points, 4, dims);
+        assert_eq!((cell.min.x, cell.max.y), (-15, 459));
+        assert_eq!(
+            closest(&voronoi.points, voronoi.metric, Point::new(-10, 400)),
+            Some(4)
+        );
+        // the bounding box alone would hold only 102 of its cells
+        assert_eq!(largest_finite(&voronoi)?, 3135);
+        // 2, 0 sits on the edge between two hull corners, and owns 2, -1 and beyond
+        let input = "0, 0\n2, 0\n4, 0\n2, 5\n2, 2";
+        let (points, dims) = parse_points(input)?;
+        let voronoi = Voronoi::new(points.clone(), dims, Metric::EuclideanSquared);
+        assert_eq!(voronoi.infinite(), vec![true, true, true, true, false]);
+        let duplicated: Vec<Point> = points.iter().chain(&points[..1]).cloned().collect();
+        let voronoi = Voronoi::new(duplicated, dims, Metric::EuclideanSquared);
+        assert_eq!(voronoi.infinite()[..2], [false, true]);
+
+        let input = "4, 0, 0\n-4, 0, 0\n0, 4, 0\n0, -4, 0\n0, 0, 4\n0, 0, -4\n0, 0, 0";
+        let (points, dims) = parse_points(input)?;
+        let voronoi = Voronoi::new(points, dims, Metric::EuclideanSquared);
+        assert_eq!(voronoi.infinite(), [vec![true; 6], vec![false]].concat());
+        Ok(())
+    }
+
+    /// The points on the edge of the convex hull in 2 dimensions, by the monotone chain.
+    fn hull_edge(points: &[Point]) -> Vec<bool> {
+        let cross = |o: Point, a: Point, b: Point| {
+            i64::from(a.x - o.x) * i64::from(b.y - o.y)
+                - i64::from(a.y - o.y) * i64::from(b.x - o.x)
+        };
+        let sorted: Vec<Point> = points
+            .iter()
+            .cloned()
+            .sorted_by_key(|p| (p.x, p.y))
+            .into_iter()
+            .dedup()
+            .collect();
+        let mut corners: Vec<Point> = vec![];
+        for pass in 0..2 {
+            let start = corners.len();
+            let ordered: Vec<Point> = if pass == 0 {
+                sorted.clone()
+            } else {
+                sorted.iter().rev().cloned().collect()
+            };
+            for &p in &ordered {
+                while corners.len() >= start + 2
+                    && cross(corners[corners.len() - 2], corners[corners.len() - 1], p) <= 0
+                {
+                    corners.pop();
+                }
+                corners.push(p);
+            }
+            corners.pop();
+        }
+        corners.push(corners[0]);
+        let between = |p: Point, a: Point, b: Point| {
+            cross(a, b, p) == 0
+                && p.x >= a.x.min(b.x)
+                && p.x <= a.x.max(b.x)
+                && p.y >= a.y.min(b.y)
+                && p.y <= a.y.max(b.y)
+        };
+        points
+            .iter()
+            .map(|&p| {
+                points.iter().filter(|&&q| q == p).count() == 1
+                    && corners.windows(2).any(|edge| between(p, edge[0], edge[1]))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_infinite_regions() {
+        for seed in 0..100 {
+            let input = generate(&mut Rng::new(seed), 2 + seed as usize % 12);
+            let (points, dims) = parse_points(&input).expect("points");
+            let voronoi = Voronoi::new(points.clone(), dims, Metric::EuclideanSquared);
+            assert_eq!(voronoi.infinite(), hull_edge(&points), "on {:?}", input);
+        }
+        // for Chebyshev, any box at least the span plus 1 bigger gives the same answer
+        for seed in 0..10 {
+            let input = generate(&mut Rng::new(seed), 6);
+            let (points, dims) = parse_points(&input).expect("points");
+            let voronoi = Voronoi::new(points.clone(), dims, Metric::Chebyshev);
+            let far = voronoi.bounds.grow(4 * voronoi.bounds.span());
+            let mut infinite = vec![false; points.len()];
+            let mut areas = vec![0; points.len()];
+            for cell in far.cells() {
+                if let Some(i) = closest(&points, Metric::Chebyshev, cell) {
+                    infinite[i] |= far.on_edge(cell);
+                    areas[i] += 1;
+                }
+            }
+            assert_eq!(voronoi.infinite(), infinite, "on {:?}", input);
+            let areas: Vec<Option<u32>> = areas
+                .into_iter()
+                .zip(infinite)
+                .map(|(area, infinite)| Some(area).filter(|_| !infinite))
+                .collect();
+            assert_eq!(voronoi.areas(), areas, "on {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_3d() -> Result<()> {
+        // a point boxed in by six others, one on the middle of each face
+        let input = "4, 0, 0\n-4, 0, 0\n0, 4, 0\n0, -4, 0\n0, 0, 4\n0, 0, -4\n0, 0, 0";
+        let (points, dims) = parse_points(input)?;
+        assert_eq!(dims, 3);
+        let voronoi = Voronoi::new(points.clone(), dims, Metric::Manhattan);
+        let areas = voronoi.areas();
+        assert!(areas[..6].iter().all(|area| area.is_none()));
+        // the middle is nearer than a face's point up to 1 step either way on each axis
+        assert_eq!(areas[6], Some(27));
+        assert_eq!(area(&points, dims, Metric::Chebyshev, 29), 185);
+        Ok(())
+    }
+
+    #[test]
+    fn test_regions() -> Result<()> {
+        let params = Params::default().with("threshold", "32");
+        assert_eq!(
+            regions(&mut INPUT.as_bytes(), &params)?,
+            "largest finite region: 17\nsafe region: 16"
+        );
+        let params = params.with("metric", "chebyshev");
+        assert_eq!(
+            regions(&mut "1, 1\n5, 5".as_bytes(), &params)?,
+            "largest finite region: none\nsafe region: 949"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let image = render(&mut INPUT.as_bytes(), &Params::default())?;
+        let rows: Vec<&str> = image.lines().collect();
+        assert_eq!(rows[..3], ["P3", "8 9", "255"]);
+        // row 1 starts with the point at 1, 1, then its infinite region
+        assert!(rows[3].starts_with("0 0 0 128 45 45 128 45 45 128 45 45 200 200 200"));
+        let params = Params::default().with("z", "3");
+        assert!(render(&mut INPUT.as_bytes(), &params).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_axis_totals() {
+        // positions -2 to 7 have totals 13, 10, 7, 4, 3, 4, 5, 8, 11, 14
+        assert_eq!(
+            axis_totals(vec![4, 1, 2], 15),
+            vec![3, 4, 4, 5, 7, 8, 10, 11, 13, 14]
+        );
+        assert_eq!(axis_totals(vec![3], 1), vec![0]);
+        assert!(axis_totals(vec![0, 9], 9).is_empty());
+    }
+
+    #[test]
+    fn test_separable_matches_scan() {
+        for &threshold in &[0, 1, 50, 200] {
+            let fast = |input: &str| {
+                let (points, dims) = parse_points(input)?;
+                Ok(separable_area(&points, dims, threshold))
+            };
+            let reference = |input: &str| {
+                let (points, dims) = parse_points(input)?;
+                Ok(scan_area(&points, dims, Metric::Manhattan, threshold))
+            };
+            DiffTest::new(&generate, &fast, &reference)
+                .sizes(1..=12)
+                .shrink_lines()
+                .assert_agree();
+        }
+        let input = "4, 0, 0\n-4, 0, 0\n0, 4, 0\n0, -4, 0\n0, 0, 4\n0, 0, -4\n0, 0, 0";
+        let (points, dims) = parse_points(input).expect("points");
+        assert_eq!(
+            separable_area(&points, dims, 40),
+            scan_area(&points, dims, Metric::Manhattan, 40)
+        );
+    }
+
+    #[test]
+    fn test_generate_round_trip() -> Result<()> {
+        let mut rng = Rng::new(6);
+        for _ in 0..100 {
+            let point = Point::random(&mut rng, 400);
+            assert_eq!(point.to_string().parse::<Point>()?, point);
+        }
+        let input = "1, 2, 0\n-3, 4, 0\n5, 6, 7";
+        let (points, dims) = parse_points(input)?;
+        assert_eq!(format_points(&points, dims), input);
+        assert_eq!(format_points(&points[..2], 2), "1, 2\n-3, 4");
+        assert_eq!(parse_points(&format_points(&points[..2], 3))?.1, 3);
+        Ok(())
     }
 }
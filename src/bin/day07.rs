@@ -1,18 +1,27 @@
-use aoc2018::{dispatch, Result};
-use failure::Error;
+use aoc2018::{Day, Params, Result, Rng};
+use failure::{bail, format_err, Error};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::BufRead;
 use std::str::FromStr;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2)
+        .generator(&generate)
+        .mode("schedule", &schedule_mode)
+        .mode("analysis", &analysis_mode)
+        .dispatch()
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq)]
 struct Instruction {
-    first: char,
-    then: char,
+    first: String,
+    then: String,
 }
 
 impl FromStr for Instruction {
@@ -21,110 +30,545 @@ impl FromStr for Instruction {
     fn from_str(s: &str) -> Result<Self> {
         lazy_static! {
             static ref RE: Regex =
-                Regex::new(r"Step (.) must be finished before step (.) can begin.")
+                Regex::new(r"^Step (\S+) must be finished before step (\S+) can begin\.$")
                     .expect("regex create");
         }
 
-        let caps = RE.captures(s).expect("regex match");
+        let caps = RE
+            .captures(s)
+            .ok_or_else(|| format_err!("not an instruction: `{}`", s))?;
         Ok(Instruction {
-            first: caps[1].chars().next().expect("regex match 1"),
-            then: caps[2].chars().next().expect("regex match 2"),
+            first: caps[1].into(),
+            then: caps[2].into(),
         })
     }
 }
 
-fn get_dependencies(input: &str) -> (HashMap<char, HashSet<char>>, HashSet<char>) {
-    let instructions: Vec<Instruction> = input
-        .split('\n')
-        .filter_map(|row| row.parse().ok())
-        .collect();
-    let mut dependencies: HashMap<char, HashSet<char>> = HashMap::new();
-    let mut all = HashSet::new();
-    for instruction in instructions {
-        all.insert(instruction.first);
-        all.insert(instruction.then);
-        let entry = dependencies
-            .entry(instruction.then)
-            .or_insert(HashSet::new());
-        (*entry).insert(instruction.first);
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Step {} must be finished before step {} can begin.",
+            self.first, self.then
+        )
     }
-    (dependencies, all)
 }
 
-fn part1(input: &str) -> Result<String> {
-    let (mut dependencies, mut all) = get_dependencies(input);
-    let mut steps = vec![];
-    loop {
-        if all.is_empty() {
-            break;
-        }
-        let dependees: HashSet<char> = dependencies.keys().map(|c| *c).collect();
-        let mut ready: Vec<&char> = all.difference(&dependees).collect();
-        ready.sort();
-        let next = ready[0].clone();
-        all.remove(&next);
-        steps.push(next);
-        let keys = dependencies.keys().map(|c| *c).collect::<Vec<_>>();
-        for key in keys {
-            let value = dependencies.get_mut(&key).unwrap();
-            value.remove(&next);
-            if value.is_empty() {
-                dependencies.remove(&key);
+/// Instructions over the first `size` letters (at most 26), forming a random acyclic graph.
+fn generate_instructions(rng: &mut Rng, size: usize) -> Vec<Instruction> {
+    let mut steps: Vec<char> = (b'A'..=b'Z').take(size.max(2)).map(char::from).collect();
+    rng.shuffle(&mut steps);
+    let mut instructions = vec![];
+    for (i, &then) in steps.iter().enumerate().skip(1) {
+        // every step but the first depends on at least one earlier one
+        let instruction = |first: char| Instruction {
+            first: first.to_string(),
+            then: then.to_string(),
+        };
+        instructions.push(instruction(steps[rng.below(i)]));
+        for &first in steps[..i].iter() {
+            if rng.one_in(4) && !instructions.contains(&instruction(first)) {
+                instructions.push(instruction(first));
+            }
+        }
+    }
+    rng.shuffle(&mut instructions);
+    instructions
+}
+
+fn generate(rng: &mut Rng, size: usize) -> String {
+    generate_instructions(rng, size)
+        .iter()
+        .map(|instruction| instruction.to_string())
+        .join("\n")
+}
+
+/// Tasks, numbered in name order, and which ones each has to wait for.
+#[derive(Debug)]
+struct Graph {
+    tasks: Vec<String>,
+    prerequisites: Vec<Vec<usize>>,
+    dependents: Vec<Vec<usize>>,
+}
+
+impl Graph {
+    fn new(instructions: &[Instruction]) -> Result<Self> {
+        let tasks: Vec<String> = instructions
+            .iter()
+            .flat_map(|i| vec![i.first.clone(), i.then.clone()])
+            .sorted()
+            .into_iter()
+            .dedup()
+            .collect();
+        let index: HashMap<&str, usize> = tasks
+            .iter()
+            .enumerate()
+            .map(|(i, task)| (task.as_str(), i))
+            .collect();
+        let mut prerequisites = vec![vec![]; tasks.len()];
+        let mut dependents = vec![vec![]; tasks.len()];
+        for instruction in instructions {
+            let (first, then) = (index[&*instruction.first], index[&*instruction.then]);
+            if !prerequisites[then].contains(&first) {
+                prerequisites[then].push(first);
+                dependents[first].push(then);
+            }
+        }
+        let graph = Graph {
+            tasks,
+            prerequisites,
+            dependents,
+        };
+        graph.check_acyclic()?;
+        Ok(graph)
+    }
+
+    fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// The tasks in an order where each comes after everything it waits for.
+    fn topological(&self) -> Option<Vec<usize>> {
+        Some(self.partial_order()).filter(|order| order.len() == self.len())
+    }
+
+    /// Names a cycle if there is one. Every task left out of the topological order waits
+    /// for another one left out, so following those waits must come back round.
+    fn check_acyclic(&self) -> Result<()> {
+        let order = self.partial_order();
+        if order.len() == self.len() {
+            return Ok(());
+        }
+        let mut sorted = vec![false; self.len()];
+        for t in order {
+            sorted[t] = true;
+        }
+        let start = (0..self.len())
+            .find(|&t| !sorted[t])
+            .expect("unsorted task");
+        let mut path = vec![start];
+        loop {
+            let last = *path.last().expect("path");
+            let before = *self.prerequisites[last]
+                .iter()
+                .find(|&&p| !sorted[p])
+                .expect("an unsorted prerequisite");
+            if let Some(pos) = path.iter().position(|&t| t == before) {
+                // in the order the tasks must finish, from the first by name
+                let mut cycle: Vec<usize> = path[pos..].iter().rev().cloned().collect();
+                let first = (0..cycle.len()).min_by_key(|&i| cycle[i]).expect("cycle");
+                cycle.rotate_left(first);
+                cycle.push(cycle[0]);
+                let names = cycle.iter().map(|&t| self.tasks[t].as_str()).join(" -> ");
+                bail!("dependency cycle: {}", names);
             }
+            path.push(before);
         }
     }
-    Ok(steps.iter().collect())
-}
-
-fn work(input: &str, n_workers: usize, cost: u32) -> u32 {
-    let (mut dependencies, mut all) = get_dependencies(input);
-    let mut steps = 0;
-    let mut workers = vec![('.', 0); n_workers];
-    loop {
-        if all.is_empty() && workers.iter().all(|w| w.1 == 0) {
-            break;
-        }
-        let dependees: HashSet<char> = dependencies.keys().map(|c| *c).collect();
-        let mut ready: Vec<char> = all.difference(&dependees).map(|c| *c).collect();
-        ready.sort();
-        ready.reverse();
-        for i in 0..n_workers {
-            if workers[i].1 == 0 {
-                if let Some(next) = ready.pop() {
-                    workers[i] = (next, cost + (next as u32 - 'A' as u32) + 1);
-                    all.remove(&next);
-                } else {
-                    break;
+
+    /// The tasks a topological sort gets through before a cycle stops it.
+    fn partial_order(&self) -> Vec<usize> {
+        let mut waiting: Vec<usize> = self.prerequisites.iter().map(|p| p.len()).collect();
+        let mut order: Vec<usize> = (0..self.len()).filter(|&t| waiting[t] == 0).collect();
+        let mut next = 0;
+        while next < order.len() {
+            for &dependent in &self.dependents[order[next]] {
+                waiting[dependent] -= 1;
+                if waiting[dependent] == 0 {
+                    order.push(dependent);
                 }
             }
+            next += 1;
+        }
+        order
+    }
+
+    /// The longest chain of durations, which no number of workers can beat, and its length.
+    /// Ties go alphabetically.
+    fn critical_path(&self, durations: &[u32]) -> (Vec<usize>, u32) {
+        let remaining = self.remaining(durations);
+        let longest = |tasks: &mut dyn Iterator<Item = usize>| {
+            tasks.min_by_key(|&t| (std::cmp::Reverse(remaining[t]), t))
+        };
+        let mut path: Vec<usize> = longest(&mut (0..self.len())).into_iter().collect();
+        while let Some(next) =
+            longest(&mut self.dependents[*path.last().expect("path")].iter().cloned())
+        {
+            path.push(next);
+        }
+        let length = path.first().map_or(0, |&t| remaining[t]);
+        (path, length)
+    }
+
+    /// For each task, the longest chain of durations from its start to the end.
+    fn remaining(&self, durations: &[u32]) -> Vec<u32> {
+        let order = self.topological().expect("checked acyclic");
+        let mut remaining = vec![0; self.len()];
+        for &task in order.iter().rev() {
+            let after = self.dependents[task]
+                .iter()
+                .map(|&d| remaining[d])
+                .max()
+                .unwrap_or(0);
+            remaining[task] = durations[task] + after;
+        }
+        remaining
+    }
+}
+
+fn parse_graph(input: &str) -> Result<Graph> {
+    let instructions: Vec<Instruction> = input
+        .split('\n')
+        .map(|row| row.parse())
+        .collect::<Result<_>>()?;
+    Graph::new(&instructions)
+}
+
+/// The puzzle's durations: `cost` plus the step's place in the alphabet.
+fn letter_durations(graph: &Graph, cost: u32) -> Result<Vec<u32>> {
+    graph
+        .tasks
+        .iter()
+        .map(|task| match task.as_bytes() {
+            [letter @ b'A'..=b'Z'] => Ok(cost + u32::from(letter - b'A') + 1),
+            _ => bail!(
+                "step `{}` has no duration of its own, as it isn't a letter",
+                task
+            ),
+        })
+        .collect()
+}
+
+/// Durations from a file of `task seconds` lines; blank lines and `#` comments are skipped.
+fn file_durations(graph: &Graph, text: &str) -> Result<Vec<u32>> {
+    let mut durations: Vec<Option<u32>> = vec![None; graph.len()];
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (task, seconds) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [task, seconds] => (task, seconds),
+            _ => bail!("durations line {}: expected `task seconds`", n + 1),
+        };
+        let seconds = seconds
+            .parse()
+            .map_err(|err| format_err!("durations line {}: {}", n + 1, err))?;
+        match graph.tasks.iter().position(|t| t == task) {
+            Some(i) => durations[i] = Some(seconds),
+            None => bail!("durations line {}: no task `{}`", n + 1, task),
+        }
+    }
+    durations
+        .iter()
+        .zip(&graph.tasks)
+        .map(|(duration, task)| duration.ok_or_else(|| format_err!("no duration for `{}`", task)))
+        .collect()
+}
+
+/// Which ready task an idle worker picks first; ties always go alphabetically.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Policy {
+    Alphabetical,
+    LongestFirst,
+    CriticalPath,
+}
+
+impl FromStr for Policy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "alphabetical" => Ok(Policy::Alphabetical),
+            "longest" => Ok(Policy::LongestFirst),
+            "critical" => Ok(Policy::CriticalPath),
+            _ => bail!(
+                "expected `alphabetical`, `longest` or `critical`, found `{}`",
+                s
+            ),
         }
-        for i in 0..n_workers {
-            if workers[i].1 > 0 {
-                workers[i] = (workers[i].0, workers[i].1 - 1);
+    }
+}
+
+impl Policy {
+    /// Each task's place in the queue, lowest first.
+    fn ranks(self, graph: &Graph, durations: &[u32]) -> Vec<usize> {
+        let weights: Vec<u32> = match self {
+            Policy::Alphabetical => vec![0; graph.len()],
+            Policy::LongestFirst => durations.to_vec(),
+            Policy::CriticalPath => graph.remaining(durations),
+        };
+        // tasks are numbered alphabetically, so that settles the ties
+        let order = (0..graph.len()).sorted_by_key(|&t| (std::cmp::Reverse(weights[t]), t));
+        let mut ranks = vec![0; graph.len()];
+        for (rank, task) in order.into_iter().enumerate() {
+            ranks[task] = rank;
+        }
+        ranks
+    }
+}
+
+/// One task done by one worker.
+#[derive(Debug, Clone, PartialEq)]
+struct Assignment {
+    worker: usize,
+    task: usize,
+    start: u32,
+    end: u32,
+}
+
+/// Runs the tasks on `workers` workers, jumping from one finish time to the next.
+///
+/// Whenever tasks finish, everything they release is ready at once, and idle workers,
+/// lowest numbered first, take ready tasks in the policy's order.
+fn schedule(
+    graph: &Graph,
+    durations: &[u32],
+    workers: usize,
+    policy: Policy,
+) -> Result<Vec<Assignment>> {
+    if workers == 0 {
+        bail!("there must be at least one worker");
+    }
+    let ranks = policy.ranks(graph, durations);
+    let mut waiting: Vec<usize> = graph.prerequisites.iter().map(|p| p.len()).collect();
+    let mut ready: BTreeSet<(usize, usize)> = (0..graph.len())
+        .filter(|&t| waiting[t] == 0)
+        .map(|t| (ranks[t], t))
+        .collect();
+    let mut busy: Vec<Option<Assignment>> = vec![None; workers];
+    let mut done = vec![];
+    let mut time = 0;
+    while done.len() < graph.len() {
+        loop {
+            let mut changed = false;
+            for slot in busy.iter_mut() {
+                if slot.as_ref().is_some_and(|a| a.end <= time) {
+                    let finished = slot.take().expect("finished");
+                    for &dependent in &graph.dependents[finished.task] {
+                        waiting[dependent] -= 1;
+                        if waiting[dependent] == 0 {
+                            ready.insert((ranks[dependent], dependent));
+                        }
+                    }
+                    done.push(finished);
+                    changed = true;
+                }
             }
-            if workers[i].1 == 0 && workers[i].0 != '.' {
-                let ready_char = workers[i].0;
-                // println!("ready: {}, {}", i, ready_char);
-                workers[i] = ('.', 0);
-                let keys = dependencies.keys().map(|c| *c).collect::<Vec<_>>();
-                for key in keys {
-                    let value = dependencies.get_mut(&key).unwrap();
-                    value.remove(&ready_char);
-                    if value.is_empty() {
-                        dependencies.remove(&key);
+            for (worker, slot) in busy.iter_mut().enumerate() {
+                if slot.is_none() {
+                    if let Some(&next) = ready.iter().next() {
+                        ready.remove(&next);
+                        let task = next.1;
+                        *slot = Some(Assignment {
+                            worker,
+                            task,
+                            start: time,
+                            end: time + durations[task],
+                        });
+                        changed = true;
                     }
                 }
             }
+            if !changed {
+                break;
+            }
+        }
+        match busy.iter().filter_map(|a| a.as_ref()).map(|a| a.end).min() {
+            Some(end) => time = end,
+            None => break,
         }
-        // println!("step: {:3}, workers: {:?}", steps, workers);
-        // println!("{:?}", dependencies);
-        steps += 1;
     }
-    steps
+    done.sort_by_key(|a| (a.start, a.worker));
+    Ok(done)
+}
+
+fn finish_time(assignments: &[Assignment]) -> u32 {
+    assignments.iter().map(|a| a.end).max().unwrap_or(0)
+}
+
+/// The names of the tasks run together, or separated by commas if any is longer than a letter.
+fn task_list(graph: &Graph, tasks: impl Iterator<Item = usize>) -> String {
+    let separator = if graph.tasks.iter().all(|t| t.chars().count() == 1) {
+        ""
+    } else {
+        ", "
+    };
+    tasks.map(|t| graph.tasks[t].as_str()).join(separator)
+}
+
+/// The puzzle's second-by-second table of what each worker is doing and what is done.
+fn table(graph: &Graph, assignments: &[Assignment], workers: usize) -> String {
+    let width = graph
+        .tasks
+        .iter()
+        .map(|t| t.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Worker 1".len());
+    let mut header = vec!["Second".to_string()];
+    header.extend((1..=workers).map(|w| format!("{:^1$}", format!("Worker {}", w), width)));
+    header.push("Done".into());
+    let mut lines = vec![header.join("   ")];
+    let finished = assignments.iter().sorted_by_key(|a| (a.end, a.worker));
+    for second in 0..=finish_time(assignments) {
+        let mut row = vec![format!("{:<6}", format!("{:>4}", second))];
+        for worker in 0..workers {
+            let task = assignments
+                .iter()
+                .find(|a| a.worker == worker && a.start <= second && second < a.end)
+                .map_or(".", |a| graph.tasks[a.task].as_str());
+            row.push(format!("{:^1$}", task, width));
+        }
+        let done = finished.iter().filter(|a| a.end <= second).map(|a| a.task);
+        row.push(task_list(graph, done));
+        lines.push(row.join("   ").trim_end().to_string());
+    }
+    lines.join("\n")
+}
+
+/// A bar per worker, with `scale` seconds to a column; each task is its name padded with `-`,
+/// and a task starting partway through a column takes it over.
+fn gantt(graph: &Graph, assignments: &[Assignment], workers: usize, scale: u32) -> String {
+    let scale = scale.max(1);
+    let columns = finish_time(assignments).div_ceil(scale) as usize;
+    let mut axis = vec![' '; columns];
+    for column in (0..columns).step_by(10) {
+        let label = (column as u32 * scale).to_string();
+        for (i, c) in label.chars().enumerate() {
+            if column + i < columns {
+                axis[column + i] = c;
+            }
+        }
+    }
+    let mut lines = vec![format!("{:10}{}", "", axis.iter().collect::<String>())];
+    for worker in 0..workers {
+        let mut bar = vec!['.'; columns];
+        for a in assignments
+            .iter()
+            .filter(|a| a.worker == worker && a.end > a.start)
+        {
+            let (first, last) = ((a.start / scale) as usize, ((a.end - 1) / scale) as usize);
+            let mut name = graph.tasks[a.task].chars();
+            for cell in bar[first..=last].iter_mut() {
+                *cell = name.next().unwrap_or('-');
+            }
+        }
+        let label = format!("Worker {}", worker + 1);
+        lines.push(format!("{:<10}{}", label, bar.iter().collect::<String>()));
+    }
+    lines.iter().map(|line| line.trim_end()).join("\n")
+}
+
+/// The assignments as a JSON array, with workers numbered from 1 as in the table.
+fn to_json(graph: &Graph, assignments: &[Assignment]) -> String {
+    let assignments: Vec<_> = assignments
+        .iter()
+        .map(|a| {
+            json!({
+                "worker": a.worker + 1,
+                "task": graph.tasks[a.task],
+                "start": a.start,
+                "end": a.end,
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&assignments).expect("serialise schedule")
+}
+
+/// What a pipeline needs: its critical path, and the fewest workers that finish in that
+/// time under `policy`.
+struct Analysis {
+    path: Vec<usize>,
+    length: u32,
+    workers: usize,
+}
+
+fn analyse(graph: &Graph, durations: &[u32], policy: Policy) -> Result<Analysis> {
+    let (path, length) = graph.critical_path(durations);
+    // with a worker per task every task starts once it can, so that always reaches the
+    // length; but a greedy schedule can finish later with more workers than with fewer,
+    // so a count that reaches it doesn't mean every larger one does, and bisecting
+    // could skip the fewest; try every count instead
+    for workers in 1..=graph.len().max(1) {
+        if finish_time(&schedule(graph, durations, workers, policy)?) == length {
+            return Ok(Analysis {
+                path,
+                length,
+                workers,
+            });
+        }
+    }
+    unreachable!("a worker per task finishes on the critical path")
+}
+
+fn part1(input: &str) -> Result<String> {
+    let graph = parse_graph(input)?;
+    let durations = vec![1; graph.len()];
+    Ok(schedule(&graph, &durations, 1, Policy::Alphabetical)?
+        .iter()
+        .map(|a| graph.tasks[a.task].as_str())
+        .collect())
+}
+
+fn work(input: &str, workers: usize, cost: u32) -> Result<u32> {
+    let graph = parse_graph(input)?;
+    let durations = letter_durations(&graph, cost)?;
+    Ok(finish_time(&schedule(
+        &graph,
+        &durations,
+        workers,
+        Policy::Alphabetical,
+    )?))
 }
 
 fn part2(input: &str) -> Result<u32> {
-    Ok(work(input, 5, 60))
+    work(input, 5, 60)
+}
+
+/// The graph from the input, with durations from `durations=<file>` or `cost=<n>`.
+fn read_graph(reader: &mut dyn BufRead, params: &Params) -> Result<(Graph, Vec<u32>)> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    let graph = parse_graph(input.trim())?;
+    let durations = match params.get_str("durations") {
+        Some(path) => file_durations(&graph, &fs::read_to_string(path)?)?,
+        None => letter_durations(&graph, params.get("cost", 60)?)?,
+    };
+    Ok((graph, durations))
+}
+
+fn schedule_mode(reader: &mut dyn BufRead, params: &Params) -> Result<String> {
+    let (graph, durations) = read_graph(reader, params)?;
+    let workers = params.get("workers", 5)?;
+    let policy = params.get("policy", Policy::Alphabetical)?;
+    let assignments = schedule(&graph, &durations, workers, policy)?;
+    let finish = finish_time(&assignments);
+    match params.get_str("format").unwrap_or("time") {
+        "time" => Ok(finish.to_string()),
+        "table" => Ok(table(&graph, &assignments, workers)),
+        "gantt" => {
+            let scale = params.get("scale", finish.div_ceil(100))?;
+            Ok(gantt(&graph, &assignments, workers, scale))
+        }
+        "json" => Ok(to_json(&graph, &assignments)),
+        _ => bail!("option `format` must be `time`, `table`, `gantt` or `json`"),
+    }
+}
+
+fn analysis_mode(reader: &mut dyn BufRead, params: &Params) -> Result<String> {
+    let (graph, durations) = read_graph(reader, params)?;
+    let policy = params.get("policy", Policy::Alphabetical)?;
+    let analysis = analyse(&graph, &durations, policy)?;
+    let path = analysis
+        .path
+        .iter()
+        .map(|&t| graph.tasks[t].as_str())
+        .join(" -> ");
+    Ok(format!(
+        "critical path: {} ({})\nunlimited workers: {}\nworkers needed: {}",
+        path, analysis.length, analysis.length, analysis.workers
+    ))
 }
 
 #[cfg(test)]
@@ -145,7 +589,194 @@ Step F must be finished before step E can begin.";
     }
 
     #[test]
-    fn test_part2() {
-        assert_eq!(work(INPUT, 2, 0), 15)
+    fn test_part2() -> Result<()> {
+        Ok(assert_eq!(work(INPUT, 2, 0)?, 15))
+    }
+
+    const NAMED: &str = "Step fetch must be finished before step build can begin.
+Step build must be finished before step test can begin.
+Step build must be finished before step package can begin.
+Step lint must be finished before step package can begin.
+Step docs must be finished before step package can begin.";
+
+    const DURATIONS: &str = "# seconds per task
+fetch 3
+build 5
+test 10
+package 2
+lint 1
+docs 4";
+
+    #[test]
+    fn test_named_tasks() -> Result<()> {
+        let graph = parse_graph(NAMED)?;
+        assert_eq!(
+            graph.tasks,
+            vec!["build", "docs", "fetch", "lint", "package", "test"]
+        );
+        let durations = file_durations(&graph, DURATIONS)?;
+        assert_eq!(durations, vec![5, 4, 3, 1, 2, 10]);
+        assert_eq!(graph.remaining(&durations), vec![15, 6, 18, 3, 2, 10]);
+        assert!(letter_durations(&graph, 60).is_err());
+        assert!(file_durations(&graph, "fetch 3").is_err());
+        assert!(file_durations(&graph, &format!("{}\ndeploy 1", DURATIONS)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_policies() -> Result<()> {
+        let input = format!(
+            "{}\nStep archive must be finished before step package can begin.\n\
+             Step zip must be finished before step package can begin.",
+            NAMED
+        );
+        let graph = parse_graph(&input)?;
+        let durations = file_durations(&graph, &format!("{}\narchive 8\nzip 6", DURATIONS))?;
+        let finish =
+            |policy| -> Result<u32> { Ok(finish_time(&schedule(&graph, &durations, 2, policy)?)) };
+        // archive and docs start first, so fetch starts late, at 4
+        assert_eq!(finish(Policy::Alphabetical)?, 22);
+        // archive and zip are the longest, and hold fetch up until 8
+        assert_eq!(finish(Policy::LongestFirst)?, 26);
+        // fetch leads the longest chain, so it starts straight away
+        assert_eq!(finish(Policy::CriticalPath)?, 21);
+        assert!(schedule(&graph, &durations, 0, Policy::Alphabetical).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_analysis() -> Result<()> {
+        let graph = parse_graph(INPUT)?;
+        let durations = letter_durations(&graph, 0)?;
+        let (path, length) = graph.critical_path(&durations);
+        assert_eq!(task_list(&graph, path.into_iter()), "CFE");
+        assert_eq!(length, 14);
+        // two workers take 15 seconds whichever way they pick
+        for &policy in &[Policy::Alphabetical, Policy::CriticalPath] {
+            assert_eq!(analyse(&graph, &durations, policy)?.workers, 3);
+        }
+
+        let graph = parse_graph(NAMED)?;
+        let durations = file_durations(&graph, DURATIONS)?;
+        let analysis = analyse(&graph, &durations, Policy::Alphabetical)?;
+        assert_eq!(analysis.path, vec![2, 0, 5]);
+        assert_eq!((analysis.length, analysis.workers), (18, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_table() -> Result<()> {
+        let graph = parse_graph(INPUT)?;
+        let durations = letter_durations(&graph, 0)?;
+        let assignments = schedule(&graph, &durations, 2, Policy::Alphabetical)?;
+        assert_eq!(
+            assignments[..3],
+            [
+                Assignment {
+                    worker: 0,
+                    task: 2,
+                    start: 0,
+                    end: 3
+                },
+                Assignment {
+                    worker: 0,
+                    task: 0,
+                    start: 3,
+                    end: 4
+                },
+                Assignment {
+                    worker: 1,
+                    task: 5,
+                    start: 3,
+                    end: 9
+                },
+            ]
+        );
+        let expected = "Second   Worker 1   Worker 2   Done
+   0        C          .
+   1        C          .
+   2        C          .
+   3        A          F       C
+   4        B          F       CA
+   5        B          F       CA
+   6        D          F       CAB
+   7        D          F       CAB
+   8        D          F       CAB
+   9        D          .       CABF
+  10        E          .       CABFD
+  11        E          .       CABFD
+  12        E          .       CABFD
+  13        E          .       CABFD
+  14        E          .       CABFD
+  15        .          .       CABFDE";
+        Ok(assert_eq!(table(&graph, &assignments, 2), expected))
+    }
+
+    #[test]
+    fn test_gantt() -> Result<()> {
+        let graph = parse_graph(INPUT)?;
+        let durations = letter_durations(&graph, 0)?;
+        let assignments = schedule(&graph, &durations, 2, Policy::Alphabetical)?;
+        let expected = "          0         10
+Worker 1  C--AB-D---E----
+Worker 2  ...F-----......";
+        assert_eq!(gantt(&graph, &assignments, 2, 1), expected);
+        let expected = "          0
+Worker 1  CBDE-
+Worker 2  .F-..";
+        assert_eq!(gantt(&graph, &assignments, 2, 3), expected);
+
+        let graph = parse_graph(NAMED)?;
+        let durations = file_durations(&graph, DURATIONS)?;
+        let assignments = schedule(&graph, &durations, 2, Policy::CriticalPath)?;
+        let expected = "          0         10
+Worker 1  fetbuildtest------
+Worker 2  docsl...pa........";
+        Ok(assert_eq!(gantt(&graph, &assignments, 2, 1), expected))
+    }
+
+    #[test]
+    fn test_json() -> Result<()> {
+        let graph = parse_graph("Step A must be finished before step B can begin.")?;
+        let assignments = schedule(&graph, &[2, 1], 1, Policy::Alphabetical)?;
+        let value: serde_json::Value = serde_json::from_str(&to_json(&graph, &assignments))?;
+        Ok(assert_eq!(
+            value,
+            json!([
+                { "worker": 1, "task": "A", "start": 0, "end": 2 },
+                { "worker": 1, "task": "B", "start": 2, "end": 3 },
+            ])
+        ))
+    }
+
+    #[test]
+    fn test_cycle() {
+        let input = format!(
+            "{}\nStep test must be finished before step fetch can begin.",
+            NAMED
+        );
+        assert_eq!(
+            parse_graph(&input).unwrap_err().to_string(),
+            "dependency cycle: build -> test -> fetch -> build"
+        );
+        let input = "Step A must be finished before step A can begin.";
+        assert_eq!(
+            parse_graph(input).unwrap_err().to_string(),
+            "dependency cycle: A -> A"
+        );
+    }
+
+    #[test]
+    fn test_generate_round_trip() -> Result<()> {
+        let instructions = generate_instructions(&mut Rng::new(7), 26);
+        for instruction in instructions.iter() {
+            assert_eq!(
+                &instruction.to_string().parse::<Instruction>()?,
+                instruction
+            );
+        }
+        let input = generate(&mut Rng::new(7), 26);
+        assert_eq!(part1(&input)?.len(), 26);
+        Ok(())
     }
 }
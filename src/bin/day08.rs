@@ -1,57 +1,250 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Params, Result, Rng};
+use failure::{bail, format_err, Error};
+use itertools::Itertools;
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2)
+        .generator(&generate)
+        .mode("tree", &tree)
+        .dispatch()
 }
 
-fn parse(data: &mut impl Iterator<Item = u32>) -> u32 {
-    let n_children = data.next().expect("not enough data to read n_children");
-    let n_metadata = data.next().expect("not enough data to read n_metadata");
-    let mut metadata = 0;
-    for _ in 0..n_children {
-        metadata += parse(data);
+/// A license tree of `size` nodes, at least one. Each node shares what is left of the
+/// budget out among its children at random, so the tree stays shallow like the puzzle's.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    fn node(rng: &mut Rng, budget: usize, data: &mut Vec<usize>) {
+        let n_children = if budget == 0 {
+            0
+        } else {
+            1 + rng.below(budget.min(6))
+        };
+        let mut cuts: Vec<usize> = (0..n_children.saturating_sub(1))
+            .map(|_| rng.below(budget - n_children + 1))
+            .collect();
+        cuts.push(0);
+        cuts.push(budget - n_children);
+        cuts.sort();
+        let n_metadata = 1 + rng.below(3);
+        data.push(n_children);
+        data.push(n_metadata);
+        for pair in cuts.windows(2).take(n_children) {
+            node(rng, pair[1] - pair[0], data);
+        }
+        for _ in 0..n_metadata {
+            data.push(1 + rng.below(n_children + 2));
+        }
     }
-    for _ in 0..n_metadata {
-        metadata += data.next().expect("not enough metadata");
+
+    let mut data = vec![];
+    node(rng, size.saturating_sub(1), &mut data);
+    data.iter().join(" ")
+}
+
+/// A license tree node.
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    children: Vec<Node>,
+    metadata: Vec<u32>,
+}
+
+/// How deep a tree may go. The parser itself doesn't recurse, but the methods below do, and
+/// this keeps them well within the stack.
+const MAX_DEPTH: usize = 1000;
+
+/// A node whose header has been read, waiting for its children.
+#[derive(Default)]
+struct Partial {
+    children: Vec<Node>,
+    n_children: usize,
+    n_metadata: usize,
+}
+
+/// Where the node on top of the stack sits, such as `root > 2 > 1` for the first child of
+/// the root's second child.
+fn path(stack: &[Partial]) -> String {
+    let mut path = "root".to_string();
+    for parent in stack.iter().rev().skip(1).rev() {
+        path.push_str(&format!(" > {}", parent.children.len() + 1));
     }
-    metadata
+    path
 }
 
-fn part1(input: &str) -> Result<u32> {
-    let mut data = input
-        .split_whitespace()
-        .filter_map(|d| d.parse::<u32>().ok());
-    Ok(parse(&mut data))
+/// The whitespace-separated tokens of the input, numbered from 1 in errors.
+struct Tokens<'a> {
+    tokens: Vec<&'a str>,
+    read: usize,
 }
 
-fn parse2(data: &mut impl Iterator<Item = u32>) -> u32 {
-    let n_children = data.next().expect("not enough data to read n_children");
-    let n_metadata = data.next().expect("not enough data to read n_metadata");
-    let mut metadata = 0;
-    if n_children == 0 {
-        for _ in 0..n_metadata {
-            metadata += data.next().expect("not enough metadata");
+impl<'a> Tokens<'a> {
+    /// The next number, which is `what` in the node on top of the stack.
+    fn next(&mut self, what: fmt::Arguments, stack: &[Partial]) -> Result<u32> {
+        let token = self.tokens.get(self.read).ok_or_else(|| {
+            format_err!(
+                "data ran out after {} tokens ({} of node {})",
+                self.read,
+                what,
+                path(stack)
+            )
+        })?;
+        self.read += 1;
+        token.parse().map_err(|_| {
+            format_err!(
+                "token {} ({} of node {}): `{}` is not a number",
+                self.read,
+                what,
+                path(stack),
+                token
+            )
+        })
+    }
+}
+
+impl Node {
+    /// Builds a tree from the flat numbers, keeping the nodes still being read on a stack.
+    fn read(input: &str, max_depth: usize) -> Result<Node> {
+        let mut tokens = Tokens {
+            tokens: input.split_whitespace().collect(),
+            read: 0,
+        };
+        let mut stack: Vec<Partial> = vec![];
+        loop {
+            if stack.len() == max_depth {
+                bail!(
+                    "tree is more than {} levels deep at token {}",
+                    max_depth,
+                    tokens.read + 1
+                );
+            }
+            stack.push(Partial::default());
+            let n_children = tokens.next(format_args!("the child count"), &stack)? as usize;
+            let n_metadata = tokens.next(format_args!("the metadata count"), &stack)? as usize;
+            let top = stack.last_mut().expect("top");
+            top.children = Vec::with_capacity(n_children.min(1024));
+            top.n_children = n_children;
+            top.n_metadata = n_metadata;
+            while let Some(top) = stack.last() {
+                if top.children.len() < top.n_children {
+                    break;
+                }
+                let n_metadata = top.n_metadata;
+                let metadata = (1..=n_metadata)
+                    .map(|i| {
+                        let what = format_args!("metadata entry {} of {}", i, n_metadata);
+                        tokens.next(what, &stack)
+                    })
+                    .collect::<Result<_>>()?;
+                let node = Node {
+                    children: stack.pop().expect("top").children,
+                    metadata,
+                };
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None if tokens.read < tokens.tokens.len() => bail!(
+                        "{} tokens left over after the tree, from token {}",
+                        tokens.tokens.len() - tokens.read,
+                        tokens.read + 1
+                    ),
+                    None => return Ok(node),
+                }
+            }
         }
-        return metadata;
     }
-    let mut child_data = vec![];
-    for _ in 0..n_children {
-        child_data.push(parse2(data));
+
+    /// The puzzle's first metric: all the metadata in the tree added up.
+    fn metadata_sum(&self) -> u32 {
+        let own: u32 = self.metadata.iter().sum();
+        own + self.children.iter().map(Node::metadata_sum).sum::<u32>()
     }
-    for _ in 0..n_metadata {
-        let child_id = data.next().expect("not enough metadata") - 1;
-        if let Some(&child_meta) = child_data.get(child_id as usize) {
-            metadata += child_meta;
+
+    /// The puzzle's second metric: a leaf's metadata added up, or for any other node the
+    /// values of the children its metadata points to, counting from 1.
+    fn value(&self) -> u32 {
+        if self.children.is_empty() {
+            return self.metadata.iter().sum();
         }
+        let values: Vec<u32> = self.children.iter().map(Node::value).collect();
+        self.metadata
+            .iter()
+            .filter_map(|&m| values.get((m as usize).wrapping_sub(1)))
+            .sum()
+    }
+
+    /// The tree drawn one node per line, each showing its metadata.
+    fn pretty(&self) -> String {
+        fn draw(node: &Node, prefix: &str, branch: &str, lines: &mut Vec<String>) {
+            let metadata = if node.metadata.is_empty() {
+                "(no metadata)".to_string()
+            } else {
+                node.metadata.iter().join(" ")
+            };
+            lines.push(format!("{}{}{}", prefix, branch, metadata));
+            let prefix = format!(
+                "{}{}",
+                prefix,
+                match branch {
+                    "" => "",
+                    "└── " => "    ",
+                    _ => "│   ",
+                }
+            );
+            for (i, child) in node.children.iter().enumerate() {
+                let last = i + 1 == node.children.len();
+                draw(child, &prefix, if last { "└── " } else { "├── " }, lines);
+            }
+        }
+
+        let mut lines = vec![];
+        draw(self, "", "", &mut lines);
+        lines.join("\n")
+    }
+}
+
+impl FromStr for Node {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Node::read(s, MAX_DEPTH)
     }
-    metadata
+}
+
+/// Back to the flat format: the header, the children, then the metadata.
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.children.len(), self.metadata.len())?;
+        for child in &self.children {
+            write!(f, " {}", child)?;
+        }
+        for m in &self.metadata {
+            write!(f, " {}", m)?;
+        }
+        Ok(())
+    }
+}
+
+fn part1(input: &str) -> Result<u32> {
+    Ok(input.parse::<Node>()?.metadata_sum())
 }
 
 fn part2(input: &str) -> Result<u32> {
-    let mut data = input
-        .split_whitespace()
-        .filter_map(|d| d.parse::<u32>().ok());
-    Ok(parse2(&mut data))
+    Ok(input.parse::<Node>()?.value())
+}
+
+fn tree(reader: &mut dyn BufRead, params: &Params) -> Result<String> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    let max_depth = params.get("max_depth", MAX_DEPTH)?;
+    if !(1..=MAX_DEPTH).contains(&max_depth) {
+        bail!("option `max_depth` must be between 1 and {}", MAX_DEPTH);
+    }
+    let root = Node::read(&input, max_depth)?;
+    match params.get_str("format").unwrap_or("pretty") {
+        "pretty" => Ok(root.pretty()),
+        "flat" => Ok(root.to_string()),
+        _ => bail!("option `format` must be `pretty` or `flat`"),
+    }
 }
 
 #[cfg(test)]
@@ -73,4 +266,94 @@ mod tests {
             66
         ))
     }
+
+    #[test]
+    fn test_tree() -> Result<()> {
+        let root: Node = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2".parse()?;
+        assert_eq!(root.children[1].children[0].metadata, vec![99]);
+        assert_eq!(root.to_string(), "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2");
+        let expected = "1 1 2
+├── 10 11 12
+└── 2
+    └── 99";
+        assert_eq!(root.pretty(), expected);
+        let root: Node = "2 0 1 0 0 0 0 1 7".parse()?;
+        let expected = "(no metadata)
+├── (no metadata)
+│   └── (no metadata)
+└── 7";
+        Ok(assert_eq!(root.pretty(), expected))
+    }
+
+    #[test]
+    fn test_depth_limit() -> Result<()> {
+        // a line of nodes, each the only child of the one before
+        let chain = |depth: usize| {
+            let mut numbers = vec![];
+            for _ in 1..depth {
+                numbers.extend(&[1, 1]);
+            }
+            numbers.extend(&[0, 1]);
+            numbers.extend(vec![1; depth]);
+            numbers.iter().join(" ")
+        };
+        assert_eq!(
+            Node::read(&chain(100_000), MAX_DEPTH)
+                .unwrap_err()
+                .to_string(),
+            "tree is more than 1000 levels deep at token 2001"
+        );
+        let root = Node::read(&chain(MAX_DEPTH), MAX_DEPTH)?;
+        assert_eq!((root.metadata_sum(), root.value()), (1000, 1));
+
+        let deep = chain(100_000);
+        let params = Params::default().with("max_depth", "1000000");
+        assert_eq!(
+            tree(&mut deep.as_bytes(), &params).unwrap_err().to_string(),
+            "option `max_depth` must be between 1 and 1000"
+        );
+        let params = Params::default().with("max_depth", "10");
+        assert_eq!(
+            tree(&mut deep.as_bytes(), &params).unwrap_err().to_string(),
+            "tree is more than 10 levels deep at token 21"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |input: &str| input.parse::<Node>().unwrap_err().to_string();
+        assert_eq!(
+            error(""),
+            "data ran out after 0 tokens (the child count of node root)"
+        );
+        assert_eq!(
+            error("2 3 0 3 10 11 12 1 1 0 1 99 2 1"),
+            "data ran out after 14 tokens (metadata entry 2 of 3 of node root)"
+        );
+        assert_eq!(
+            error("2 3 0 3 10 11 12 1 1 0"),
+            "data ran out after 10 tokens (the metadata count of node root > 2 > 1)"
+        );
+        assert_eq!(
+            error("2 3 0 3 10 eleven 12 1 1 0 1 99 2 1 1 2"),
+            "token 6 (metadata entry 2 of 3 of node root > 1): `eleven` is not a number"
+        );
+        assert_eq!(
+            error("2 3 0 3 10 11 12 1 -1 0 1 99 2 1 1 2"),
+            "token 9 (the metadata count of node root > 2): `-1` is not a number"
+        );
+        assert_eq!(
+            error("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2 0 1 7"),
+            "3 tokens left over after the tree, from token 17"
+        );
+    }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let input = generate(&mut Rng::new(8), 50);
+        part1(&input)?;
+        part2(&input)?;
+        Ok(assert_eq!(input.parse::<Node>()?.to_string(), input))
+    }
 }
@@ -1,74 +1,287 @@
-use aoc2018::{dispatch, Result};
-use std::collections::VecDeque;
+use aoc2018::{Day, Params, Result, Rng};
+use failure::{bail, err_msg, format_err, Error};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::io::BufRead;
+use std::str::FromStr;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2)
+        .generator(&generate)
+        .mode("replay", &replay)
+        .dispatch()
 }
 
+/// A game with up to `100 * size` marbles.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    format!(
+        "{} players; last marble is worth {} points",
+        rng.range(2, 30),
+        rng.range(25, 25 + 100 * size as i64)
+    )
+}
+
+/// The game's settings, from the puzzle's sentence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Game {
+    players: usize,
+    last_marble: u32,
+}
+
+impl FromStr for Game {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        lazy_static! {
+            static ref RE: Regex =
+                Regex::new(r"^(\d+) players?; last marble is worth (\d+) points?$")
+                    .expect("regex create");
+        }
+
+        let caps = RE
+            .captures(s.trim())
+            .ok_or_else(|| format_err!("not a marble game: `{}`", s.trim()))?;
+        let game = Game {
+            players: caps[1].parse()?,
+            last_marble: caps[2].parse()?,
+        };
+        if game.players == 0 {
+            bail!("there must be at least one player");
+        }
+        Ok(game)
+    }
+}
+
+/// The marbles in play, as a doubly linked ring. Every marble has its own number, so that
+/// is its place in the arena too, and removed marbles just leave their slots behind.
 #[derive(Debug)]
-struct Marbles {
-    marbles: VecDeque<u64>,
-    current_pos: usize,
+struct Circle {
+    next: Vec<u32>,
+    prev: Vec<u32>,
+    current: u32,
 }
 
-impl Marbles {
-    fn new() -> Self {
-        let mut marbles = VecDeque::new();
-        marbles.push_back(0);
-        Marbles {
-            marbles: marbles,
-            current_pos: 0,
+impl Circle {
+    /// Marble 0 on its own, with room for marbles up to `last`.
+    fn new(last: u32) -> Self {
+        let mut next = Vec::with_capacity(last as usize + 1);
+        let mut prev = Vec::with_capacity(last as usize + 1);
+        next.push(0);
+        prev.push(0);
+        Circle {
+            next,
+            prev,
+            current: 0,
         }
     }
 
-    fn insert(&mut self, next: u64) {
-        let val = self.marbles.pop_front().unwrap();
-        self.marbles.push_back(val);
-        self.marbles.push_back(next);
+    /// Places the next marble between the two clockwise of the current one, and makes it
+    /// current.
+    fn insert(&mut self) {
+        let marble = self.next.len() as u32;
+        let before = self.next[self.current as usize];
+        let after = self.next[before as usize];
+        self.next.push(after);
+        self.prev.push(before);
+        self.next[before as usize] = marble;
+        self.prev[after as usize] = marble;
+        self.current = marble;
     }
 
-    fn remove(&mut self) -> u64 {
+    /// Takes out the marble seven counter-clockwise of the current one, making the one
+    /// clockwise of it current.
+    fn remove(&mut self) -> u32 {
+        let mut removed = self.current;
         for _ in 0..7 {
-            let val = self.marbles.pop_back().unwrap();
-            self.marbles.push_front(val);
+            removed = self.prev[removed as usize];
         }
-        let removed = self.marbles.pop_back().unwrap();
-        let val = self.marbles.pop_front().unwrap();
-        self.marbles.push_back(val);
+        let (before, after) = (self.prev[removed as usize], self.next[removed as usize]);
+        self.next[before as usize] = after;
+        self.prev[after as usize] = before;
+        self.current = after;
         removed
     }
+
+    /// Plays the next marble, returning the marble taken out if it scores. A scoring marble
+    /// is kept rather than placed, but still takes up its slot.
+    fn place(&mut self) -> Option<u32> {
+        let marble = self.next.len() as u32;
+        if marble.is_multiple_of(23) {
+            self.next.push(marble);
+            self.prev.push(marble);
+            Some(self.remove())
+        } else {
+            self.insert();
+            None
+        }
+    }
+
+    /// The marbles clockwise from marble 0.
+    fn marbles(&self) -> Vec<u32> {
+        let mut marbles = vec![0];
+        let mut marble = self.next[0];
+        while marble != 0 {
+            marbles.push(marble);
+            marble = self.next[marble as usize];
+        }
+        marbles
+    }
+
+    /// The marbles as the puzzle shows them, each right-aligned in `width` columns, with
+    /// the current one in brackets.
+    fn render(&self, width: usize) -> String {
+        let mut line = String::new();
+        for marble in self.marbles() {
+            let number = marble.to_string();
+            if marble == self.current {
+                line.push_str(&format!("{:>1$})", format!("({}", number), width));
+            } else if line.ends_with(')') {
+                line.push_str(&format!("{:>1$}", number, width - 1));
+            } else {
+                line.push_str(&format!("{:>1$}", number, width));
+            }
+        }
+        line
+    }
+}
+
+/// What happened on one turn. Players are numbered from 0.
+#[derive(Debug, Clone, PartialEq)]
+struct Turn {
+    player: usize,
+    marble: u32,
+    removed: Option<u32>,
+    points: u64,
+}
+
+/// A game being played, a turn at a time.
+struct Play {
+    game: Game,
+    circle: Circle,
+    scores: Vec<u64>,
 }
 
-fn place(marbles: &mut Marbles, next: u64) -> u64 {
-    if next > 0 && next % 23 == 0 {
-        return next + marbles.remove();
-    } else {
-        marbles.insert(next);
-        return 0;
+impl Play {
+    fn new(game: Game) -> Self {
+        Play {
+            game,
+            circle: Circle::new(game.last_marble),
+            scores: vec![0; game.players],
+        }
+    }
+
+    fn scores(&self) -> &[u64] {
+        &self.scores
+    }
+
+    /// The player with the highest score, the first of them if there is a tie.
+    fn winner(&self) -> usize {
+        (0..self.scores.len())
+            .max_by_key(|&p| (self.scores[p], std::cmp::Reverse(p)))
+            .expect("at least one player")
+    }
+
+    /// The puzzle's line for the circle as it stands: who played last, then the marbles.
+    fn render(&self) -> String {
+        let marble = self.circle.next.len() - 1;
+        let player = match marble {
+            0 => "-".to_string(),
+            _ => ((marble - 1) % self.game.players + 1).to_string(),
+        };
+        let label = format!("[{}]", player);
+        let players = self.game.players.to_string().len();
+        let width = self.game.last_marble.to_string().len().max(2) + 1;
+        format!("{:<2$}{}", label, self.circle.render(width), players + 2)
     }
 }
 
-fn play(n_players: usize, n_marbles: u64) -> u64 {
-    let mut scores = vec![0; n_players];
-    let mut current_player = 0;
-    let mut marbles = Marbles::new();
-    for next in 1..=n_marbles {
-        scores[current_player] += place(&mut marbles, next);
-        current_player = (current_player + 1) % n_players;
+impl Iterator for Play {
+    type Item = Turn;
+
+    fn next(&mut self) -> Option<Turn> {
+        let marble = self.circle.next.len() as u32;
+        if marble > self.game.last_marble {
+            return None;
+        }
+        let player = (marble as usize - 1) % self.game.players;
+        let removed = self.circle.place();
+        let points = removed.map_or(0, |removed| u64::from(marble) + u64::from(removed));
+        self.scores[player] += points;
+        Some(Turn {
+            player,
+            marble,
+            removed,
+            points,
+        })
     }
-    *scores.iter().max().unwrap()
+}
+
+fn play(n_players: usize, last_marble: u32) -> u64 {
+    let mut play = Play::new(Game {
+        players: n_players,
+        last_marble,
+    });
+    play.by_ref().for_each(drop);
+    play.scores()[play.winner()]
 }
 
 fn part1(input: &str) -> Result<u64> {
-    let numbers: Vec<u64> = input
-        .split_whitespace()
-        .filter_map(|x| x.parse().ok())
-        .collect();
-    Ok(play(numbers[0] as usize, numbers[1]))
+    let game: Game = input.parse()?;
+    Ok(play(game.players, game.last_marble))
+}
+
+fn part2(input: &str) -> Result<u64> {
+    let game: Game = input.parse()?;
+    let last_marble = game
+        .last_marble
+        .checked_mul(100)
+        .ok_or_else(|| err_msg("too many marbles to play a hundred times over"))?;
+    Ok(play(game.players, last_marble))
 }
 
-fn part2(_input: &str) -> Result<i64> {
-    Ok(0)
+/// Drawing the circle after every turn takes space that grows with the square of the game.
+const MAX_DRAWN: u32 = 1000;
+
+fn replay(reader: &mut dyn BufRead, params: &Params) -> Result<String> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    let mut play = Play::new(input.parse()?);
+    let mut lines = vec![];
+    match params.get_str("format").unwrap_or("scores") {
+        "scores" => play.by_ref().for_each(drop),
+        "events" => {
+            for turn in play.by_ref() {
+                lines.push(match turn.removed {
+                    Some(removed) => format!(
+                        "player {} keeps marble {} and takes marble {}, scoring {}",
+                        turn.player + 1,
+                        turn.marble,
+                        removed,
+                        turn.points
+                    ),
+                    None => format!("player {} places marble {}", turn.player + 1, turn.marble),
+                });
+            }
+            lines.push(String::new());
+        }
+        "circle" => {
+            if play.game.last_marble > MAX_DRAWN {
+                bail!("too many marbles to draw: at most {}", MAX_DRAWN);
+            }
+            lines.push(play.render());
+            while play.next().is_some() {
+                lines.push(play.render());
+            }
+            lines.push(String::new());
+        }
+        _ => bail!("option `format` must be `scores`, `events` or `circle`"),
+    }
+    lines.push("Player  Score".to_string());
+    for (player, score) in play.scores().iter().enumerate() {
+        lines.push(format!("{:>6}  {}", player + 1, score));
+    }
+    lines.push(format!("winner: player {}", play.winner() + 1));
+    Ok(lines.join("\n"))
 }
 
 #[cfg(test)]
@@ -83,6 +296,124 @@ mod tests {
         ))
     }
 
+    #[test]
+    fn test_part2() -> Result<()> {
+        Ok(assert_eq!(
+            part2("9 players; last marble is worth 25 points")?,
+            22563
+        ))
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let game: Game = "10 players; last marble is worth 1618 points\n".parse()?;
+        assert_eq!(
+            game,
+            Game {
+                players: 10,
+                last_marble: 1618
+            }
+        );
+        assert_eq!(
+            "1 player; last marble is worth 1 point".parse::<Game>()?,
+            Game {
+                players: 1,
+                last_marble: 1
+            }
+        );
+        assert!("10 players; last marble is worth 1618"
+            .parse::<Game>()
+            .is_err());
+        assert!("10 players; 1618 points".parse::<Game>().is_err());
+        assert!("0 players; last marble is worth 1618 points"
+            .parse::<Game>()
+            .is_err());
+        assert!(part2("1 player; last marble is worth 4294967295 points").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_circle() {
+        let mut circle = Circle::new(25);
+        for _ in 0..22 {
+            circle.place();
+        }
+        assert_eq!(
+            circle.marbles(),
+            vec![0, 16, 8, 17, 4, 18, 9, 19, 2, 20, 10, 21, 5, 22, 11, 1, 12, 6, 13, 3, 14, 7, 15]
+        );
+        assert_eq!(circle.place(), Some(9));
+        assert_eq!(circle.current, 19);
+        assert_eq!(
+            circle.marbles(),
+            vec![0, 16, 8, 17, 4, 18, 19, 2, 20, 10, 21, 5, 22, 11, 1, 12, 6, 13, 3, 14, 7, 15]
+        );
+    }
+
+    #[test]
+    fn test_turns() -> Result<()> {
+        let mut play = Play::new("9 players; last marble is worth 25 points".parse()?);
+        assert_eq!(
+            play.nth(22),
+            Some(Turn {
+                player: 4,
+                marble: 23,
+                removed: Some(9),
+                points: 32
+            })
+        );
+        assert_eq!(play.by_ref().count(), 2);
+        assert_eq!(play.scores(), &[0, 0, 0, 0, 32, 0, 0, 0, 0]);
+        assert_eq!(play.winner(), 4);
+
+        let mut play = Play::new("3 players; last marble is worth 1 point".parse()?);
+        play.by_ref().for_each(drop);
+        assert_eq!((play.scores(), play.winner()), (&[0, 0, 0][..], 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let mut play = Play::new("9 players; last marble is worth 25 points".parse()?);
+        let mut lines = vec![play.render()];
+        while play.next().is_some() {
+            lines.push(play.render());
+        }
+        let expected = "[-] (0)
+[1]  0 (1)
+[2]  0 (2) 1
+[3]  0  2  1 (3)
+[4]  0 (4) 2  1  3
+[5]  0  4  2 (5) 1  3
+[6]  0  4  2  5  1 (6) 3
+[7]  0  4  2  5  1  6  3 (7)
+[8]  0 (8) 4  2  5  1  6  3  7
+[9]  0  8  4 (9) 2  5  1  6  3  7
+[1]  0  8  4  9  2(10) 5  1  6  3  7
+[2]  0  8  4  9  2 10  5(11) 1  6  3  7
+[3]  0  8  4  9  2 10  5 11  1(12) 6  3  7
+[4]  0  8  4  9  2 10  5 11  1 12  6(13) 3  7
+[5]  0  8  4  9  2 10  5 11  1 12  6 13  3(14) 7
+[6]  0  8  4  9  2 10  5 11  1 12  6 13  3 14  7(15)
+[7]  0(16) 8  4  9  2 10  5 11  1 12  6 13  3 14  7 15
+[8]  0 16  8(17) 4  9  2 10  5 11  1 12  6 13  3 14  7 15
+[9]  0 16  8 17  4(18) 9  2 10  5 11  1 12  6 13  3 14  7 15
+[1]  0 16  8 17  4 18  9(19) 2 10  5 11  1 12  6 13  3 14  7 15
+[2]  0 16  8 17  4 18  9 19  2(20)10  5 11  1 12  6 13  3 14  7 15
+[3]  0 16  8 17  4 18  9 19  2 20 10(21) 5 11  1 12  6 13  3 14  7 15
+[4]  0 16  8 17  4 18  9 19  2 20 10 21  5(22)11  1 12  6 13  3 14  7 15
+[5]  0 16  8 17  4 18(19) 2 20 10 21  5 22 11  1 12  6 13  3 14  7 15
+[6]  0 16  8 17  4 18 19  2(24)20 10 21  5 22 11  1 12  6 13  3 14  7 15
+[7]  0 16  8 17  4 18 19  2 24 20(25)10 21  5 22 11  1 12  6 13  3 14  7 15";
+        Ok(assert_eq!(lines.join("\n"), expected))
+    }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        part1(&generate(&mut Rng::new(9), 10))?;
+        Ok(())
+    }
+
     #[test]
     fn test_play() {
         assert_eq!(play(9, 25), 32);
@@ -1,8 +1,10 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
 use failure::Error;
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::collections::HashSet;
+use std::fmt;
 use std::str::FromStr;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -46,8 +48,51 @@ impl FromStr for Point {
     }
 }
 
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "position=<{:>6}, {:>6}> velocity=<{:>2}, {:>2}>",
+            self.px, self.py, self.vx, self.vy
+        )
+    }
+}
+
+/// `size` points that line up into a 10 row high "message" after the returned number of
+/// seconds.
+fn generate_points(rng: &mut Rng, size: usize) -> (Vec<Point>, i64) {
+    let seconds = rng.range(10, 10 + 100 * size as i64);
+    let width = 10 + size as i64 / 2;
+    let points = (0..size.max(2))
+        .map(|_| {
+            let (x, y) = (rng.range(0, width), rng.range(0, 9));
+            let (vx, vy) = loop {
+                match (rng.range(-5, 5), rng.range(-5, 5)) {
+                    (0, 0) => continue,
+                    velocity => break velocity,
+                }
+            };
+            Point {
+                px: x - seconds * vx,
+                py: y - seconds * vy,
+                vx,
+                vy,
+            }
+        })
+        .collect();
+    (points, seconds)
+}
+
+fn generate(rng: &mut Rng, size: usize) -> String {
+    generate_points(rng, size)
+        .0
+        .iter()
+        .map(|point| point.to_string())
+        .join("\n")
+}
+
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
 }
 
 fn size(points: &Vec<Point>) -> i64 {
@@ -164,4 +209,15 @@ position=<-3,  6> velocity=< 2, -1>";
     fn test_part1() -> Result<()> {
         Ok(assert_eq!(part1(INPUT)?, 3))
     }
+
+    #[test]
+    fn test_generate_round_trip() -> Result<()> {
+        let (points, seconds) = generate_points(&mut Rng::new(10), 50);
+        for point in points.iter() {
+            assert_eq!(&point.to_string().parse::<Point>()?, point);
+        }
+        let input = points.iter().map(|point| point.to_string()).join("\n");
+        assert_eq!(part1(&input)?, seconds);
+        Ok(())
+    }
 }
@@ -1,9 +1,14 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
 use itertools::Itertools;
 use std::cmp;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
+}
+
+/// A grid serial number; the grid is always 300x300 so `size` is unused.
+fn generate(rng: &mut Rng, _size: usize) -> String {
+    rng.range(1, 9999).to_string()
 }
 
 fn power(x: i32, y: i32, serial: i32) -> i32 {
@@ -81,6 +86,12 @@ mod tests {
         assert_eq!(power(101, 153, 71), 4);
     }
 
+    #[test]
+    fn test_generate() -> Result<()> {
+        part1(&generate(&mut Rng::new(11), 0))?;
+        Ok(())
+    }
+
     #[test]
     fn test_part1a() -> Result<()> {
         Ok(assert_eq!(part1("18")?, "33, 45"))
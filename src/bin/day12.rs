@@ -1,10 +1,10 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
 use failure::Error;
 use std::collections::HashMap;
 use std::str::FromStr;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
 }
 
 struct Rule {
@@ -39,93 +39,153 @@ impl FromStr for Rule {
     }
 }
 
-fn part1(input: &str) -> Result<i32> {
-    let mut rows = input.split('\n');
-    let initial = rows
-        .next()
-        .expect("next")
-        .split_whitespace()
-        .nth(2)
-        .expect("1")
-        .chars()
-        .filter_map(char_to_bool)
-        .collect::<Vec<_>>();
-    rows.next();
-    let rules: Vec<Rule> = rows.filter_map(|row| row.parse().ok()).collect();
-    let rule_map: HashMap<_, bool> = rules.iter().map(|r| (&r.before[..], r.after)).collect();
-    // println!("{:?}", rule_map);
-    // println!("{:?}", initial.iter().map(|&b| if b {'#'} else {'.'}).collect::<String>());
-    let mut pots = vec![false; 60];
-    pots.extend(&initial);
-    pots.extend(&vec![false; 60]);
-    // println!("{}", pots.len());
-    for _ in 0..20 {
-        // fn foo(w: Vec<char>) -> char {
-        let foo = |w| {
-            // println!("{:?}", w);
-            *rule_map.get(&w).unwrap_or(&false)
-        };
-        pots = pots.windows(5).map(foo).collect();
-        // initial = initial.windows(5).map(|w| .expect("missing")).collect();
-        // println!("{}", pots.len());
+fn bool_to_char(b: bool) -> char {
+    if b {
+        '#'
+    } else {
+        '.'
     }
-    // println!("{}", pots.iter().map(|&b| if b {'#'} else {'.'}).collect::<String>());
-    // println!("{:?}", (-20..(20+initial.len() as i32)).zip(pots.clone()).map(|(i, p)| if p {i} else {0}).collect::<Vec<_>>());
-    Ok((-20..(20 + initial.len() as i32))
-        .zip(pots)
-        .map(|(i, p)| if p { i } else { 0 })
-        .sum())
 }
 
-fn part2(input: &str) -> Result<String> {
-    let mut rows = input.split('\n');
-    let initial = rows
-        .next()
-        .expect("next")
-        .split_whitespace()
-        .nth(2)
-        .expect("1")
-        .chars()
-        .filter_map(char_to_bool)
-        .collect::<Vec<_>>();
-    rows.next();
-    let rules: Vec<Rule> = rows.filter_map(|row| row.parse().ok()).collect();
-    let rule_map: HashMap<_, bool> = rules.iter().map(|r| (&r.before[..], r.after)).collect();
-    let mut start_pos = 0;
-    let mut prev;
-    let mut prev_score = 0;
-    let mut pots = vec![];
-    pots.extend(&initial);
-    let mut generation = 1;
+/// An initial state of `size` pots and a full set of 32 rules. `.....` never grows a plant,
+/// otherwise the pots would fill up infinitely.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    let initial: String = (0..size.max(1))
+        .map(|_| bool_to_char(rng.one_in(2)))
+        .collect();
+    let mut rows = vec![format!("initial state: {}", initial), "".into()];
+    for rule in 0..32 {
+        let before: String = (0..5)
+            .rev()
+            .map(|bit| bool_to_char(rule & (1 << bit) != 0))
+            .collect();
+        let after = rule != 0 && rng.one_in(2);
+        rows.push(format!("{} => {}", before, bool_to_char(after)));
+    }
+    rows.join("\n")
+}
+
+struct Garden {
+    initial: Vec<bool>,
+    rules: HashMap<Vec<bool>, bool>,
+}
+
+impl FromStr for Garden {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut rows = s.split('\n');
+        let initial = rows
+            .next()
+            .expect("next")
+            .split_whitespace()
+            .nth(2)
+            .expect("1")
+            .chars()
+            .filter_map(char_to_bool)
+            .collect::<Vec<_>>();
+        rows.next();
+        let rules = rows
+            .filter_map(|row| row.parse::<Rule>().ok())
+            .map(|r| (r.before, r.after))
+            .collect();
+        Ok(Garden { initial, rules })
+    }
+}
+
+/// The pots from the first to the last plant, and the number of the first one.
+#[derive(Debug, Clone, PartialEq)]
+struct Pots {
+    first: i64,
+    pattern: Vec<bool>,
+}
+
+impl Pots {
+    fn new(first: i64, mut pattern: Vec<bool>) -> Self {
+        let leading = pattern.iter().take_while(|&&p| !p).count();
+        let trailing = pattern.iter().rev().take_while(|&&p| !p).count();
+        if leading == pattern.len() {
+            return Pots {
+                first: 0,
+                pattern: vec![],
+            };
+        }
+        pattern.truncate(pattern.len() - trailing);
+        pattern.drain(..leading);
+        Pots {
+            first: first + leading as i64,
+            pattern,
+        }
+    }
+
+    fn step(&self, rules: &HashMap<Vec<bool>, bool>) -> Self {
+        let mut padded = vec![false; 4];
+        padded.extend(&self.pattern);
+        padded.extend(&[false; 4]);
+        let pattern = padded
+            .windows(5)
+            .map(|w| *rules.get(w).unwrap_or(&false))
+            .collect();
+        Pots::new(self.first - 2, pattern)
+    }
+
+    fn plants(&self) -> i64 {
+        self.pattern.iter().filter(|&&p| p).count() as i64
+    }
+
+    fn score(&self) -> i64 {
+        (self.first..)
+            .zip(&self.pattern)
+            .map(|(i, &p)| if p { i } else { 0 })
+            .sum()
+    }
+}
+
+/// Steps through every generation.
+fn simulate(garden: &Garden, generations: u64) -> i64 {
+    let mut pots = Pots::new(0, garden.initial.clone());
+    for _ in 0..generations {
+        pots = pots.step(&garden.rules);
+    }
+    pots.score()
+}
+
+/// Steps until the pattern comes back, possibly shifted, and extrapolates from there.
+fn extrapolate(garden: &Garden, generations: u64) -> i64 {
+    let mut history = vec![Pots::new(0, garden.initial.clone())];
+    let mut seen: HashMap<Vec<bool>, usize> = HashMap::new();
     loop {
-        let step = |w| *rule_map.get(&w).unwrap_or(&false);
-        prev = pots.clone();
-        for _ in 0..4 {
-            pots.insert(0, false);
-            pots.push(false);
+        let generation = history.len() - 1;
+        let pots = &history[generation];
+        if generation as u64 == generations {
+            return pots.score();
         }
-        start_pos -= 2;
-        pots = pots.windows(5).map(step).collect();
-        let score = pots
-            .iter()
-            .enumerate()
-            .map(|(i, &p)| if p { i as i64 + start_pos } else { 0 })
-            .sum();
-
-        if prev.iter().zip(pots.iter().skip(3)).all(|(a, b)| a == b) {
-            return Ok(format!(
-                "{}",
-                score + (50_000_000_000 - generation) * (score - prev_score)
-            ));
+        if let Some(&start) = seen.get(&pots.pattern) {
+            let period = (generation - start) as u64;
+            let shift = pots.first - history[start].first;
+            let remaining = generations - generation as u64;
+            let end = &history[start + (remaining % period) as usize];
+            let total_shift = shift * (remaining / period + 1) as i64;
+            return end.score() + total_shift * end.plants();
         }
-        prev_score = score;
-        generation += 1;
+        seen.insert(pots.pattern.clone(), generation);
+        let next = pots.step(&garden.rules);
+        history.push(next);
     }
 }
 
+fn part1(input: &str) -> Result<i64> {
+    Ok(simulate(&input.parse()?, 20))
+}
+
+fn part2(input: &str) -> Result<i64> {
+    Ok(extrapolate(&input.parse()?, 50_000_000_000))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aoc2018::difftest::DiffTest;
 
     const INPUT: &str = "initial state: #..#.#..##......###...###
 
@@ -148,4 +208,40 @@ mod tests {
     fn test_part1() -> Result<()> {
         Ok(assert_eq!(part1(INPUT)?, 325))
     }
+
+    #[test]
+    fn test_extrapolate() -> Result<()> {
+        let garden = INPUT.parse()?;
+        for &generations in &[0, 1, 20, 150, 301] {
+            assert_eq!(
+                extrapolate(&garden, generations),
+                simulate(&garden, generations)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_fast_matches_reference() {
+        let fast = |input: &str| Ok(extrapolate(&input.parse()?, 200));
+        let reference = |input: &str| Ok(simulate(&input.parse()?, 200));
+        DiffTest::new(&generate, &fast, &reference)
+            .sizes(1..=10)
+            .shrink_lines()
+            .assert_agree();
+    }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let input = generate(&mut Rng::new(12), 30);
+        let rules: Vec<Rule> = input
+            .split('\n')
+            .skip(2)
+            .map(|row| row.parse())
+            .collect::<Result<_>>()?;
+        assert_eq!(rules.len(), 32);
+        assert!(rules.iter().any(|r| r.before == vec![false; 5] && !r.after));
+        part1(&input)?;
+        Ok(())
+    }
 }
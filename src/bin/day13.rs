@@ -1,9 +1,12 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng, Whitespace};
 use failure::err_msg;
 use std::collections::HashMap;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2)
+        .generator(&generate)
+        .whitespace(Whitespace::Keep)
+        .dispatch()
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -185,6 +188,102 @@ fn parse_input(input: &str) -> (HashMap<Coor, Track>, HashMap<Coor, Train>) {
     (map, trains)
 }
 
+/// `size` rectangular loops with an odd number of carts on their straight sections.
+///
+/// All loop corners get distinct x and y coordinates, so tracks only ever meet by crossing
+/// at right angles, where they become intersections. The first loop runs round the edge
+/// with every other one inside it, and carries two carts going opposite ways round, so
+/// there is always a crash.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    let loops = size.max(1);
+    let extent = 4 * loops + 4;
+    let mut xs: Vec<usize> = (1..extent - 1).collect();
+    let mut ys = xs.clone();
+    rng.shuffle(&mut xs);
+    rng.shuffle(&mut ys);
+    for coords in [&mut xs, &mut ys].iter_mut() {
+        coords.insert(0, 0);
+        coords.insert(1, extent - 1);
+    }
+
+    let mut grid = vec![vec![' '; extent]; extent];
+    fn draw(grid: &mut [Vec<char>], x: usize, y: usize, c: char) {
+        grid[y][x] = if grid[y][x] == ' ' { c } else { '+' };
+    }
+    for i in 0..loops {
+        let (x0, x1) = (xs[2 * i].min(xs[2 * i + 1]), xs[2 * i].max(xs[2 * i + 1]));
+        let (y0, y1) = (ys[2 * i].min(ys[2 * i + 1]), ys[2 * i].max(ys[2 * i + 1]));
+        for x in (x0 + 1)..x1 {
+            draw(&mut grid, x, y0, '-');
+            draw(&mut grid, x, y1, '-');
+        }
+        for y in (y0 + 1)..y1 {
+            draw(&mut grid, x0, y, '|');
+            draw(&mut grid, x1, y, '|');
+        }
+        grid[y0][x0] = '/';
+        grid[y0][x1] = '\\';
+        grid[y1][x0] = '\\';
+        grid[y1][x1] = '/';
+    }
+
+    let mut straights = vec![];
+    for (y, row) in grid.iter().enumerate() {
+        for (x, &c) in row.iter().enumerate() {
+            if c == '-' || c == '|' {
+                straights.push((x, y));
+            }
+        }
+    }
+    rng.shuffle(&mut straights);
+    let (outer, inner): (Vec<_>, Vec<_>) = straights
+        .into_iter()
+        .partition(|&(x, y)| x == 0 || y == 0 || x == extent - 1 || y == extent - 1);
+    // clockwise round the edge, then anticlockwise
+    for (&(x, y), clockwise) in outer.iter().zip(&[true, false]) {
+        let along = if y == 0 {
+            '>'
+        } else if x == extent - 1 {
+            'v'
+        } else if y == extent - 1 {
+            '<'
+        } else {
+            '^'
+        };
+        grid[y][x] = if *clockwise { along } else { reverse(along) };
+    }
+    let rest = outer
+        .iter()
+        .skip(2)
+        .chain(&inner)
+        .cloned()
+        .collect::<Vec<_>>();
+    for &(x, y) in rest.iter().take(loops / 2 * 2 + 1) {
+        let carts = if grid[y][x] == '-' {
+            ['<', '>']
+        } else {
+            ['^', 'v']
+        };
+        grid[y][x] = *rng.choose(&carts);
+    }
+
+    grid.iter()
+        .map(|row| row.iter().collect::<String>().trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_end()
+        .to_string()
+}
+
+fn reverse(cart: char) -> char {
+    match cart {
+        '>' => '<',
+        '<' => '>',
+        '^' => 'v',
+        _ => '^',
+    }
+}
+
 fn part1(input: &str) -> Result<String> {
     let (map, mut trains) = parse_input(input);
     loop {
@@ -260,4 +359,29 @@ mod tests {
             "(6, 4)"
         ))
     }
+
+    #[test]
+    fn test_generate() {
+        let input = generate(&mut Rng::new(13), 6);
+        let (map, trains) = parse_input(&input);
+        assert_eq!(trains.len(), 9);
+        for pos in trains.keys() {
+            match map.get(pos) {
+                Some(Track::Horizontal) | Some(Track::Vertical) => {}
+                track => panic!("train on {:?}", track),
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_small() -> Result<()> {
+        for size in 0..4 {
+            for seed in 0..20 {
+                let input = generate(&mut Rng::new(seed), size);
+                assert!(parse_input(&input).1.len() >= 3);
+                part1(&input)?;
+            }
+        }
+        Ok(())
+    }
 }
@@ -1,11 +1,18 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
+}
+
+/// A puzzle number of `size` digits (between 1 and 6, so part 2 stays quick).
+fn generate(rng: &mut Rng, size: usize) -> String {
+    let digits = size.clamp(1, 6) as u32;
+    rng.range(10_i64.pow(digits - 1), 10_i64.pow(digits) - 1)
+        .to_string()
 }
 
 fn part1(input: &str) -> Result<String> {
-    let rounds = input.parse::<usize>().unwrap();
+    let rounds = input.parse::<usize>()?;
 
     let mut e1 = 0;
     let mut e2 = 1;
@@ -34,7 +41,7 @@ fn part1(input: &str) -> Result<String> {
 }
 
 fn part2(input: &str) -> Result<usize> {
-    let mut end = input.parse::<u32>().unwrap();
+    let mut end = input.parse::<u32>()?;
 
     let mut target = vec![];
     for pos in (0..input.len()).rev() {
@@ -111,6 +118,12 @@ mod tests {
         Ok(assert_eq!(part1("2018")?, "5941429882"))
     }
 
+    #[test]
+    fn test_bad_input() {
+        assert!(part1("509671x").is_err());
+        assert!(part2("").is_err());
+    }
+
     #[test]
     fn test_part2a() -> Result<()> {
         Ok(assert_eq!(part2("51589")?, 9))
@@ -128,5 +141,13 @@ mod tests {
         Ok(assert_eq!(part2("59414")?, 2018))
     }
 
+    #[test]
+    fn test_generate() -> Result<()> {
+        let input = generate(&mut Rng::new(14), 4);
+        assert_eq!(input.len(), 4);
+        assert_eq!(part1(&input)?.len(), 10);
+        Ok(())
+    }
+
     // 107073195 too high
 }
@@ -1,11 +1,11 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
 use failure::Error;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::mem;
 use std::str::FromStr;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -403,6 +403,29 @@ impl Input {
     }
 }
 
+/// A `size` x `size` cave inside an outer wall, with `size / 2` goblins and as many elves.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    let n = size.max(3);
+    let mut grid = vec![vec!['#'; n + 2]; n + 2];
+    let mut open = vec![];
+    for (y, row) in grid.iter_mut().enumerate().skip(1).take(n) {
+        for (x, cell) in row.iter_mut().enumerate().skip(1).take(n) {
+            if !rng.one_in(5) {
+                *cell = '.';
+                open.push((x, y));
+            }
+        }
+    }
+    rng.shuffle(&mut open);
+    for (i, &(x, y)) in open.iter().take(n / 2 * 2).enumerate() {
+        grid[y][x] = if i % 2 == 0 { 'G' } else { 'E' };
+    }
+    grid.iter()
+        .map(|row| row.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 fn part1(input: &str) -> Result<i32> {
     let mut game: Game = input.parse()?;
     // game.print();
@@ -449,4 +472,12 @@ mod tests {
     fn test_part1() -> Result<()> {
         Ok(assert_eq!(part1("")?, 0))
     }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let game: Game = generate(&mut Rng::new(15), 10).parse()?;
+        assert_eq!(game.units.len(), 10);
+        assert_eq!(game.terrain.len(), 12 * 12);
+        Ok(())
+    }
 }
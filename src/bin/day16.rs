@@ -1,10 +1,12 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
+use failure::err_msg;
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::{CaptureMatches, Captures, Regex};
 use std::collections::{HashMap, HashSet};
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
 }
 
 type RegType = i32;
@@ -204,14 +206,15 @@ impl Cpu {
     }
 }
 
+const OP_NAMES: [&str; 16] = [
+    "addr", "addi", "mulr", "muli", "banr", "bani", "borr", "bori", "setr", "seti", "gtir", "gtri",
+    "gtrr", "eqir", "eqri", "eqrr",
+];
+
 fn try_all(before: Registers, after: Registers, op: Op) -> Vec<String> {
     let mut result = vec![];
-    let funcs = [
-        "addr", "addi", "mulr", "muli", "banr", "bani", "borr", "bori", "setr", "seti", "gtir",
-        "gtri", "gtrr", "eqir", "eqri", "eqrr",
-    ];
 
-    for name in funcs.iter() {
+    for name in OP_NAMES.iter() {
         let mut cpu = Cpu::new(before);
         cpu.dispatch(name, op[1], op[2], op[3]);
         if cpu.compare(after) {
@@ -222,50 +225,99 @@ fn try_all(before: Registers, after: Registers, op: Op) -> Vec<String> {
     result
 }
 
-fn part1(input: &str) -> Result<i32> {
-    let mut count = 0;
-    for input in InputWalker::new(input) {
-        // println!("{:?}", input);
-        if try_all(input.before, input.after, input.op).len() >= 3 {
-            count += 1;
-        }
-    }
-    Ok(count)
-}
-
-fn part2(input_str: &str) -> Result<i32> {
+/// What each opcode could be, given the samples so far.
+fn candidates<'a>(samples: impl Iterator<Item = &'a Input>) -> HashMap<i32, HashSet<String>> {
     let mut possible_ops: HashMap<i32, HashSet<String>> = HashMap::new();
-    let mut op_inputs = 0;
-    for input in InputWalker::new(&input_str) {
-        op_inputs += 1;
-        let opcode = input.op[0];
+    for input in samples {
         let current_matches: HashSet<String> = try_all(input.before, input.after, input.op)
             .into_iter()
             .collect();
         let entry = possible_ops
-            .entry(opcode)
-            .or_insert(current_matches.clone());
+            .entry(input.op[0])
+            .or_insert_with(|| current_matches.clone());
         *entry = entry.intersection(&current_matches).cloned().collect();
     }
+    possible_ops
+}
 
+/// Pins every opcode to one operation, by settling whichever has a single candidate
+/// left and taking that operation off the rest. `None` if that gets stuck.
+fn resolve(mut possible_ops: HashMap<i32, HashSet<String>>) -> Option<HashMap<i32, String>> {
     let mut ops = HashMap::new();
-    let mut total = 0;
-    while total < 16 {
-        let mut found = None;
-        for (key, possible) in possible_ops.iter() {
-            if possible.len() == 1 {
-                let op = possible.iter().next().unwrap();
-                ops.insert(key.clone(), op.clone());
-                found = Some(op.clone());
-                total += 1;
-                break;
-            }
-        }
-        let found: String = found.unwrap().to_string();
+    while ops.len() < OP_NAMES.len() {
+        let (&key, possible) = possible_ops
+            .iter()
+            .find(|(_, possible)| possible.len() == 1)?;
+        let found = possible.iter().next().expect("one candidate").clone();
         for value in possible_ops.values_mut() {
-            (*value).remove(&found);
+            value.remove(&found);
         }
+        ops.insert(key, found);
     }
+    Some(ops)
+}
+
+/// At least `size` samples, and as many more as it takes to pin every opcode down, then
+/// a `size` instruction program, under a random opcode numbering.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    let mut names = OP_NAMES.to_vec();
+    rng.shuffle(&mut names);
+    let random_op = |rng: &mut Rng| -> Op {
+        [
+            rng.below(16) as RegType,
+            rng.range(0, 3) as RegType,
+            rng.range(0, 3) as RegType,
+            rng.range(0, 3) as RegType,
+        ]
+    };
+
+    let mut samples = vec![];
+    while samples.len() < size || resolve(candidates(samples.iter())).is_none() {
+        let op = random_op(rng);
+        let before = [
+            rng.range(0, 3) as RegType,
+            rng.range(0, 3) as RegType,
+            rng.range(0, 3) as RegType,
+            rng.range(0, 3) as RegType,
+        ];
+        let mut cpu = Cpu::new(before);
+        cpu.dispatch(names[op[0] as usize], op[1], op[2], op[3]);
+        samples.push(Input {
+            before,
+            op,
+            after: cpu.registers,
+        });
+    }
+    let samples = samples.iter().map(|sample| {
+        format!(
+            "Before: {:?}\n{}\nAfter:  {:?}",
+            sample.before,
+            sample.op.iter().join(" "),
+            sample.after
+        )
+    });
+    let program = (0..size)
+        .map(|_| random_op(rng).iter().join(" "))
+        .join("\n");
+    format!("{}\n\n\n\n{}", samples.format("\n\n"), program)
+}
+
+fn part1(input: &str) -> Result<i32> {
+    let mut count = 0;
+    for input in InputWalker::new(input) {
+        // println!("{:?}", input);
+        if try_all(input.before, input.after, input.op).len() >= 3 {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+fn part2(input_str: &str) -> Result<i32> {
+    let samples: Vec<Input> = InputWalker::new(input_str).collect();
+    let op_inputs = samples.len();
+    let ops = resolve(candidates(samples.iter()))
+        .ok_or_else(|| err_msg("the samples don't pin every opcode to one operation"))?;
     println!("{:?}", ops);
     let mut cpu = Cpu::new([0, 0, 0, 0]);
     let mut count = 0;
@@ -303,4 +355,28 @@ mod tests {
     fn test_part1() -> Result<()> {
         Ok(assert_eq!(part1("")?, 0))
     }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let input = generate(&mut Rng::new(16), 40);
+        let samples: Vec<Input> = InputWalker::new(&input).collect();
+        assert!(samples.len() >= 40);
+        for sample in samples.iter() {
+            assert!(!try_all(sample.before, sample.after, sample.op).is_empty());
+        }
+        assert_eq!(OpWalker::new(&input).count(), samples.len() + 40);
+        part2(&input)?;
+        for size in 0..4 {
+            for seed in 0..10 {
+                part2(&generate(&mut Rng::new(seed), size))?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unresolved() {
+        let sample = "Before: [3, 2, 1, 1]\n9 2 1 2\nAfter:  [3, 2, 2, 1]\n\n\n\n9 0 0 0";
+        assert!(part2(sample).is_err());
+    }
 }
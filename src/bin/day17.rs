@@ -1,4 +1,5 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::{CaptureMatches, Captures, Regex};
 use std::cmp;
@@ -112,6 +113,59 @@ impl<'r, 't> Iterator for InputWalker<'r, 't> {
     }
 }
 
+impl fmt::Display for Scan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scan::Horizontal(scan) => write!(f, "x={}, y={}..{}", scan.x, scan.y0, scan.y1),
+            Scan::Vertical(scan) => write!(f, "y={}, x={}..{}", scan.y, scan.x0, scan.x1),
+        }
+    }
+}
+
+/// `size` clay basins (two walls and a floor) scattered under the spring, plus the odd
+/// ledge, in shuffled order. Basins spread downwards without limit, but sideways only as
+/// far as keeps every x at least 1.
+fn generate_scans(rng: &mut Rng, size: usize) -> Vec<Scan> {
+    let spread = 10 * size as i64 + 10;
+    let across = spread.min(490);
+    let mut scans = vec![];
+    for _ in 0..size.max(1) {
+        let x0 = (500 + rng.range(-across, across)) as u32;
+        let x1 = x0 + rng.range(2, 12) as u32;
+        let y1 = rng.range(4, spread) as u32;
+        let left_top = y1 - rng.range(1, 3) as u32;
+        let right_top = y1 - rng.range(1, 3) as u32;
+        scans.push(Scan::Horizontal(HorizontalScan {
+            x: x0,
+            y0: left_top,
+            y1,
+        }));
+        scans.push(Scan::Horizontal(HorizontalScan {
+            x: x1,
+            y0: right_top,
+            y1,
+        }));
+        scans.push(Scan::Vertical(VerticalScan { x0, x1, y: y1 }));
+        if rng.one_in(4) {
+            let x0 = (500 + rng.range(-across, across)) as u32;
+            scans.push(Scan::Vertical(VerticalScan {
+                x0,
+                x1: x0 + rng.range(0, 6) as u32,
+                y: rng.range(1, spread) as u32,
+            }));
+        }
+    }
+    rng.shuffle(&mut scans);
+    scans
+}
+
+fn generate(rng: &mut Rng, size: usize) -> String {
+    generate_scans(rng, size)
+        .iter()
+        .map(|scan| scan.to_string())
+        .join("\n")
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Terrain {
     Clay,
@@ -342,7 +396,7 @@ fn part2(input: &str) -> Result<usize> {
 }
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
 }
 
 #[cfg(test)]
@@ -377,4 +431,25 @@ y=0, x=495..495"
             0
         ))
     }
+
+    #[test]
+    fn test_generate_round_trip() {
+        let scans = generate_scans(&mut Rng::new(17), 20);
+        let text = scans.iter().map(|scan| scan.to_string()).join("\n");
+        assert_eq!(InputWalker::new(&text).collect::<Vec<_>>(), scans);
+    }
+
+    #[test]
+    fn test_generate_large() {
+        for seed in 0..10 {
+            let scans = generate_scans(&mut Rng::new(seed), 200);
+            let text = scans.iter().map(|scan| scan.to_string()).join("\n");
+            assert_eq!(InputWalker::new(&text).collect::<Vec<_>>(), scans);
+            let mut xs = scans.iter().flat_map(|scan| match scan {
+                Scan::Horizontal(scan) => vec![scan.x],
+                Scan::Vertical(scan) => vec![scan.x0, scan.x1],
+            });
+            assert!(xs.all(|x| (1..1100).contains(&x)));
+        }
+    }
 }
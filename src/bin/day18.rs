@@ -1,11 +1,22 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
 use failure::Error;
 use itertools::Itertools;
 use std::collections::HashMap;
 use std::str::FromStr;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
+}
+
+/// A `size` x `size` area of random acres (the puzzle uses 50x50).
+fn generate(rng: &mut Rng, size: usize) -> String {
+    (0..size.max(1))
+        .map(|_| {
+            (0..size.max(1))
+                .map(|_| *rng.choose(&['.', '|', '#']))
+                .collect::<String>()
+        })
+        .join("\n")
 }
 
 type Coor = (i32, i32);
@@ -192,4 +203,11 @@ mod tests {
     fn test_part1() -> Result<()> {
         Ok(assert_eq!(part1(INPUT)?, 1147))
     }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let area: Area = generate(&mut Rng::new(18), 10).parse()?;
+        assert_eq!(area.terrain.len(), 100);
+        Ok(())
+    }
 }
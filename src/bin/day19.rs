@@ -1,4 +1,4 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
 use failure::{err_msg, Error};
 use lazy_static::lazy_static;
 use regex::{CaptureMatches, Captures, Regex};
@@ -6,7 +6,7 @@ use std::fmt;
 use std::str::FromStr;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
 }
 
 type RegType = i32;
@@ -127,6 +127,57 @@ impl<'r, 't> Iterator for OpWalker<'r, 't> {
     }
 }
 
+/// The puzzle's divisor-sum program with random constants.
+///
+/// Part 1 sums the divisors of a number below about 1100. The extra amount part 2 adds
+/// is scaled by a multiplier of up to `size` (the puzzle uses 14), so `size` 0 leaves
+/// part 2 summing the same number as part 1.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    format!(
+        "#ip 3
+addi 3 16 3
+seti 1 0 4
+seti 1 0 1
+mulr 4 1 5
+eqrr 5 2 5
+addr 5 3 3
+addi 3 1 3
+addr 4 0 0
+addi 1 1 1
+gtrr 1 2 5
+addr 3 5 3
+seti 2 4 3
+addi 4 1 4
+gtrr 4 2 5
+addr 5 3 3
+seti 1 5 3
+mulr 3 3 3
+addi 2 2 2
+mulr 2 2 2
+mulr 3 2 2
+muli 2 {} 2
+addi 5 {} 5
+mulr 5 3 5
+addi 5 {} 5
+addr 2 5 2
+addr 3 0 3
+seti 0 5 3
+setr 3 9 5
+mulr 5 3 5
+addr 3 5 5
+mulr 3 5 5
+muli 5 {} 5
+mulr 5 3 5
+addr 2 5 2
+seti 0 8 0
+seti 0 5 3",
+        rng.range(1, 11),
+        rng.range(1, 9),
+        rng.range(1, 20),
+        rng.range(0, size as i64)
+    )
+}
+
 #[derive(Debug, Clone)]
 struct Cpu {
     pc: RegType,
@@ -144,6 +195,17 @@ impl Cpu {
             program,
         }
     }
+    fn from_input(input: &str, registers: Registers) -> Self {
+        let (pc_info, program) = input.split_at(5);
+        let pc_register = pc_info
+            .split_whitespace()
+            .filter_map(|p| p.parse::<usize>().ok())
+            .nth(0)
+            .unwrap();
+        let program = OpWalker::new(program).collect();
+        Cpu::new(pc_register, registers, program)
+    }
+
     fn get(&self, register: RegType) -> RegType {
         self.registers[register as usize]
     }
@@ -155,22 +217,35 @@ impl Cpu {
     // }
 
     fn run(&mut self) {
-        loop {
-            let instruction_idx = self.get(self.pc_register as i32);
-            if instruction_idx < 0 {
-                break;
+        while self.step() {}
+    }
+
+    /// Runs until about to execute instruction `target`; false if the program halts first.
+    fn run_until(&mut self, target: RegType) -> bool {
+        while self.step() {
+            if self.get(self.pc_register as i32) == target {
+                return true;
             }
-            if let Some(&op) = self.program.get(instruction_idx as usize) {
-                self.dispatch(&op.op_type, op.a, op.b, op.c);
-                let pc = self.get(self.pc_register as i32) + 1;
-                if pc < 0 || pc >= self.program.len() as i32 {
-                    break;
-                }
-                self.set(self.pc_register as i32, pc);
-            // println!("{:?}\n", self.registers);
-            } else {
-                break;
+        }
+        false
+    }
+
+    fn step(&mut self) -> bool {
+        let instruction_idx = self.get(self.pc_register as i32);
+        if instruction_idx < 0 {
+            return false;
+        }
+        if let Some(&op) = self.program.get(instruction_idx as usize) {
+            self.dispatch(&op.op_type, op.a, op.b, op.c);
+            let pc = self.get(self.pc_register as i32) + 1;
+            if pc < 0 || pc >= self.program.len() as i32 {
+                return false;
             }
+            self.set(self.pc_register as i32, pc);
+            // println!("{:?}\n", self.registers);
+            true
+        } else {
+            false
         }
     }
 
@@ -254,32 +329,36 @@ impl Cpu {
 }
 
 fn part1(input: &str) -> Result<i32> {
-    let (pc_info, program) = input.split_at(5);
-    let pc_register = pc_info
-        .split_whitespace()
-        .filter_map(|p| p.parse::<usize>().ok())
-        .nth(0)
-        .unwrap();
-    let program = OpWalker::new(program).collect();
-    let mut cpu = Cpu::new(pc_register, [0, 0, 0, 0, 0, 0], program);
+    let mut cpu = Cpu::from_input(input, [0, 0, 0, 0, 0, 0]);
     cpu.run();
     Ok(cpu.get(0))
 }
 
-fn part2(_input: &str) -> Result<i32> {
-    let n = 10551410;
-    let mut sum = 0;
-    for i in 1..=n {
-        if n % i == 0 {
-            sum += i
-        }
+/// The sum of `n`'s divisors, which can outgrow `n`'s own type.
+fn divisor_sum(n: i32) -> i64 {
+    let n = i64::from(n);
+    (1..)
+        .take_while(|i| i * i <= n)
+        .filter(|i| n % i == 0)
+        .map(|i| if i * i == n { i } else { i + n / i })
+        .sum()
+}
+
+/// The program sets up a number and then slowly sums its divisors, starting at
+/// instruction 1. Once the setup is done, the number is the largest register.
+fn part2(input: &str) -> Result<i64> {
+    let mut cpu = Cpu::from_input(input, [1, 0, 0, 0, 0, 0]);
+    if !cpu.run_until(1) {
+        return Ok(cpu.get(0).into());
     }
-    Ok(sum)
+    let n = *cpu.registers.iter().max().unwrap();
+    Ok(divisor_sum(n))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aoc2018::difftest::DiffTest;
 
     #[test]
     fn test_part1() -> Result<()> {
@@ -297,4 +376,54 @@ seti 9 0 5"
             6
         ))
     }
+
+    #[test]
+    fn test_divisor_sum() {
+        assert_eq!(divisor_sum(1), 1);
+        assert_eq!(divisor_sum(16), 31);
+        assert_eq!(divisor_sum(10551410), 18992556);
+        assert_eq!(divisor_sum(1_000_000_007), 1_000_000_008);
+        assert_eq!(divisor_sum(46340 * 46340), 6019608861);
+        assert_eq!(divisor_sum(i32::MAX - 1), 6045990912);
+        assert_eq!(divisor_sum(i32::MAX), 2147483648);
+    }
+
+    #[test]
+    fn test_fast_matches_reference() {
+        // only size 0 keeps part 2's number small enough for the program itself
+        let reference = |input: &str| {
+            let mut cpu = Cpu::from_input(input, [1, 0, 0, 0, 0, 0]);
+            cpu.run();
+            Ok(i64::from(cpu.get(0)))
+        };
+        DiffTest::new(&generate, &part2, &reference)
+            .sizes(0..=0)
+            .seeds(10)
+            .assert_agree();
+
+        // beyond that, work the number out from the constants and check it by trial division
+        let reference = |input: &str| {
+            let program: Vec<Op> = OpWalker::new(input).collect();
+            let n = 76 * i64::from(program[20].b)
+                + 22 * i64::from(program[21].b)
+                + i64::from(program[23].b)
+                + (27 * 28 + 29) * 30 * 32 * i64::from(program[31].b);
+            Ok((1..=n).filter(|i| n % i == 0).sum())
+        };
+        DiffTest::new(&generate, &part2, &reference)
+            .sizes(0..=4)
+            .seeds(5)
+            .assert_agree();
+    }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let input = generate(&mut Rng::new(19), 14);
+        let program: Vec<Op> = OpWalker::new(&input).collect();
+        assert_eq!(program.len(), 36);
+        let n = 76 * program[20].b + 22 * program[21].b + program[23].b;
+        let divisor_sum: i32 = (1..=n).filter(|i| n % i == 0).sum();
+        assert_eq!(part1(&input)?, divisor_sum);
+        Ok(())
+    }
 }
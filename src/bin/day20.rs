@@ -1,4 +1,4 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng, Whitespace};
 use failure::Error;
 use std::collections::{HashMap, VecDeque};
 use std::fmt;
@@ -6,7 +6,10 @@ use std::ops;
 use std::str::FromStr;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2)
+        .generator(&generate)
+        .whitespace(Whitespace::Keep)
+        .dispatch()
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
@@ -190,6 +193,44 @@ impl FromStr for Pattern {
     }
 }
 
+/// A route regex of about `size` steps, with nested branches and dead-end detours.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    // like the puzzle's, every non-empty route starts with a step rather than a branch
+    fn step(rng: &mut Rng, budget: &mut usize, out: &mut String) {
+        out.push(*rng.choose(&['N', 'E', 'S', 'W']));
+        *budget -= 1;
+    }
+
+    fn route(rng: &mut Rng, budget: &mut usize, depth: usize, out: &mut String) {
+        if *budget > 0 {
+            step(rng, budget, out);
+        }
+        while *budget > 0 {
+            match rng.below(8) {
+                0 if depth < 4 => {
+                    out.push('(');
+                    for alternative in 0..rng.range(2, 3) {
+                        if alternative > 0 {
+                            out.push('|');
+                        }
+                        let mut sub_budget = rng.below(12).min(*budget);
+                        *budget -= sub_budget;
+                        route(rng, &mut sub_budget, depth + 1, out);
+                    }
+                    out.push(')');
+                }
+                1 if depth > 0 => return,
+                _ => step(rng, budget, out),
+            }
+        }
+    }
+
+    let mut out = "^".to_string();
+    route(rng, &mut size.max(1), 0, &mut out);
+    out.push('$');
+    out
+}
+
 fn get_distances(pattern: &Pattern) -> HashMap<Coor, usize> {
     let edges = pattern.edges();
 
@@ -327,4 +368,14 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        for seed in 0..20 {
+            let input = generate(&mut Rng::new(seed), 100);
+            assert!(input.starts_with('^') && input.ends_with('$'));
+            part1(&input)?;
+        }
+        Ok(())
+    }
 }
@@ -1,4 +1,4 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
 use failure::{err_msg, Error};
 use lazy_static::lazy_static;
 use regex::{CaptureMatches, Captures, Regex};
@@ -6,7 +6,7 @@ use std::fmt;
 use std::str::FromStr;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
 }
 
 type RegType = i32;
@@ -127,6 +127,50 @@ impl<'r, 't> Iterator for OpWalker<'r, 't> {
     }
 }
 
+/// The puzzle's hashing program with a random starting constant.
+///
+/// The hash is masked to `8 + size` bits (24 in the puzzle, so `size` 16), which bounds
+/// how many values part 2 goes through before they repeat.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    let mask = (1_i64 << (8 + size.min(16))) - 1;
+    format!(
+        "#ip 4
+seti 123 0 2
+bani 2 456 2
+eqri 2 72 2
+addr 2 4 4
+seti 0 0 4
+seti 0 6 2
+bori 2 65536 5
+seti {seed} 6 2
+bani 5 255 1
+addr 2 1 2
+bani 2 {mask} 2
+muli 2 65899 2
+bani 2 {mask} 2
+gtir 256 5 1
+addr 1 4 4
+addi 4 1 4
+seti 27 4 4
+seti 0 7 3
+addi 3 1 1
+muli 1 256 1
+gtrr 1 5 1
+addr 1 4 4
+addi 4 1 4
+seti 25 1 4
+addi 3 1 3
+seti 17 8 4
+setr 3 6 5
+seti 7 8 4
+eqrr 2 0 1
+addr 1 4 4
+seti 5 3 4",
+        seed = rng.range(0, mask),
+        mask = mask
+    )
+}
+
 #[derive(Debug, Clone)]
 struct Cpu {
     pc: RegType,
@@ -187,6 +231,14 @@ impl Cpu {
     }
 
     fn run_fast(&mut self) -> i32 {
+        self.last_before_repeat(true)
+    }
+
+    /// Runs until the value compared at instruction 28 repeats and returns the one before.
+    ///
+    /// With `shortcut` the division loop at instruction 18 is skipped; without it this is
+    /// the plain (slow) run that the shortcut is checked against.
+    fn last_before_repeat(&mut self, shortcut: bool) -> i32 {
         use std::collections::HashSet;
         let mut seen = HashSet::new();
         let mut prev = 0;
@@ -197,7 +249,7 @@ impl Cpu {
                 break 0;
             }
             // println!("{}: {:?}", instruction_idx, self.registers);
-            if instruction_idx == 18 {
+            if shortcut && instruction_idx == 18 {
                 // faster implementation of what appears to be r3 = r5 / 256 (integer division)
                 self.set(3, self.get(5) / 256);
                 self.set(self.pc_register as i32, 26);
@@ -323,6 +375,7 @@ fn part2(input: &str) -> Result<i32> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aoc2018::difftest::DiffTest;
 
     #[test]
     fn test_part1() -> Result<()> {
@@ -340,4 +393,24 @@ seti 9 0 5"
             6
         ))
     }
+
+    #[test]
+    fn test_fast_matches_reference() {
+        let reference = |input: &str| {
+            let mut cpu = Cpu::from_input(input, [0, 0, 0, 0, 0, 0]);
+            Ok(cpu.last_before_repeat(false))
+        };
+        DiffTest::new(&generate, &part2, &reference)
+            .sizes(0..=4)
+            .seeds(10)
+            .assert_agree();
+    }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let input = generate(&mut Rng::new(21), 0);
+        assert_eq!(OpWalker::new(&input).count(), 31);
+        assert!(part2(&input)? <= 255);
+        Ok(())
+    }
 }
@@ -1,10 +1,13 @@
-use aoc2018::{dispatch, Result};
-use cached::cached;
+use aoc2018::{Day, Memo, Result, Rng};
+use failure::{err_msg, Error};
+use lazy_static::lazy_static;
+use regex::Regex;
 use std::cmp::{Ord, Ordering};
 use std::collections::{BinaryHeap, HashSet};
+use std::str::FromStr;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
 }
 
 type Target = (u32, u32);
@@ -58,44 +61,58 @@ impl Type {
             Narrow => 2,
         }
     }
-
-    fn get(depth: u32, target: (u32, u32), x: u32, y: u32) -> Self {
-        let gi = geo_index(depth, target, x, y);
-        let er = erosion(depth, gi);
-        Type::from_erosion(er)
-    }
 }
 
-fn erosion(depth: u32, geo_index: u32) -> u32 {
-    (geo_index + depth) % 20183
+/// Erosion levels, worked out as they're needed and kept for as long as the cave is.
+struct Cave {
+    depth: u32,
+    target: Target,
+    erosion: Memo<(u32, u32), u32>,
 }
 
-cached! {
-    GEO_INDEX;
-    fn geo_index(depth: u32, target: (u32, u32), x: u32, y: u32) -> u32 = {
-        if (x, y) == (0, 0) {
-            0
-        } else if (x, y) == target {
+impl Cave {
+    fn new(depth: u32, target: Target) -> Self {
+        Cave {
+            depth,
+            target,
+            erosion: Memo::new("erosion"),
+        }
+    }
+
+    fn geo_index(&mut self, x: u32, y: u32) -> u32 {
+        if (x, y) == (0, 0) || (x, y) == self.target {
             0
         } else if y == 0 {
             x * 16807
         } else if x == 0 {
             y * 48271
         } else {
-            erosion(depth, geo_index(depth, target, x - 1, y))
-                * erosion(depth, geo_index(depth, target, x, y - 1))
+            self.erosion(x - 1, y) * self.erosion(x, y - 1)
         }
     }
+
+    fn erosion(&mut self, x: u32, y: u32) -> u32 {
+        if let Some(erosion) = self.erosion.get(&(x, y)) {
+            return erosion;
+        }
+        let erosion = (self.geo_index(x, y) + self.depth) % 20183;
+        self.erosion.insert((x, y), erosion)
+    }
+
+    fn type_at(&mut self, x: u32, y: u32) -> Type {
+        Type::from_erosion(self.erosion(x, y))
+    }
 }
 
 fn calculate(depth: u32, target: (u32, u32)) -> u32 {
     let (target_x, target_y) = target;
+    let mut cave = Cave::new(depth, target);
 
     let mut risk = 0;
 
     for y in 0..=target_y {
         for x in 0..=target_x {
-            risk += Type::get(depth, target, x, y).risk();
+            risk += cave.type_at(x, y).risk();
         }
     }
 
@@ -108,6 +125,32 @@ struct Input {
     target: (u32, u32),
 }
 
+impl FromStr for Input {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"depth: (\d+)\s+target: (\d+),(\d+)").unwrap();
+        }
+
+        let caps = RE.captures(s).ok_or_else(|| err_msg("parse fail"))?;
+        Ok(Input {
+            depth: caps[1].parse()?,
+            target: (caps[2].parse()?, caps[3].parse()?),
+        })
+    }
+}
+
+/// A random depth, with a target at most `size` across and `10 * size` down.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    format!(
+        "depth: {}\ntarget: {},{}",
+        rng.range(1, 20000),
+        rng.range(0, size as i64),
+        rng.range(0, 10 * size as i64)
+    )
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct State {
     x: u32,
@@ -159,16 +202,16 @@ impl PartialOrd for State {
 }
 
 struct NeighbourIterator<'a> {
-    input: &'a Input,
+    cave: &'a mut Cave,
     seen: &'a HashSet<(u32, u32, Gear)>,
     state: &'a State,
     idx: usize,
 }
 
 impl<'a> NeighbourIterator<'a> {
-    fn new(input: &'a Input, seen: &'a HashSet<(u32, u32, Gear)>, state: &'a State) -> Self {
+    fn new(cave: &'a mut Cave, seen: &'a HashSet<(u32, u32, Gear)>, state: &'a State) -> Self {
         NeighbourIterator {
-            input,
+            cave,
             seen,
             state,
             idx: 0,
@@ -184,8 +227,7 @@ impl<'a> Iterator for NeighbourIterator<'a> {
         let x = self.state.x;
         let y = self.state.y;
         let time = self.state.time;
-        let input = *self.input;
-        let type_ = self.state.type_(input);
+        let type_ = self.state.type_(self.cave);
 
         loop {
             let (offset_x, offset_y) = match self.idx {
@@ -203,7 +245,7 @@ impl<'a> Iterator for NeighbourIterator<'a> {
 
             let next_x = offset_x.apply(x);
             let next_y = offset_y.apply(y);
-            let next_type = Type::get(input.depth, input.target, next_x, next_y);
+            let next_type = self.cave.type_at(next_x, next_y);
             let gear = if type_ == next_type {
                 self.state.gear
             } else {
@@ -236,15 +278,15 @@ impl State {
             time,
         }
     }
-    fn type_(&self, input: Input) -> Type {
-        Type::get(input.depth, input.target, self.x, self.y)
+    fn type_(&self, cave: &mut Cave) -> Type {
+        cave.type_at(self.x, self.y)
     }
     fn neighbours<'a>(
         &'a self,
-        input: &'a Input,
+        cave: &'a mut Cave,
         seen: &'a HashSet<(u32, u32, Gear)>,
     ) -> NeighbourIterator<'a> {
-        NeighbourIterator::new(&input, &seen, &self)
+        NeighbourIterator::new(cave, seen, self)
     }
 
     fn distance_to_target(&self) -> u32 {
@@ -253,13 +295,13 @@ impl State {
     }
 }
 
-fn part1(_input: &str) -> Result<u32> {
-    let depth = 7305;
-    let target = (13, 734);
-    Ok(calculate(depth, target))
+fn part1(input: &str) -> Result<u32> {
+    let input: Input = input.parse()?;
+    Ok(calculate(input.depth, input.target))
 }
 
 fn calculate2(input: Input) -> u32 {
+    let mut cave = Cave::new(input.depth, input.target);
     let mut queue = BinaryHeap::new();
     let mut seen = HashSet::new();
     let start = State {
@@ -290,24 +332,39 @@ fn calculate2(input: Input) -> u32 {
             ));
         }
 
-        for next in pos.neighbours(&input, &seen) {
+        for next in pos.neighbours(&mut cave, &seen) {
             queue.push(next);
         }
     }
 }
 
-fn part2(_input: &str) -> Result<u32> {
-    let input = Input {
-        depth: 7305,
-        target: (13, 734),
-    };
-    Ok(calculate2(input))
+fn part2(input: &str) -> Result<u32> {
+    Ok(calculate2(input.parse()?))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse() -> Result<()> {
+        assert_eq!(
+            "depth: 510\ntarget: 10,10".parse::<Input>()?,
+            Input {
+                depth: 510,
+                target: (10, 10)
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let input: Input = generate(&mut Rng::new(22), 10).parse()?;
+        assert!(input.target.0 <= 10 && input.target.1 <= 100);
+        Ok(())
+    }
+
     #[test]
     fn test_calculate() {
         assert_eq!(calculate(510, (10, 10)), 114);
@@ -1,9 +1,11 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::{CaptureMatches, Captures, Regex};
+use std::fmt;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -24,6 +26,32 @@ impl Bot {
     }
 }
 
+impl fmt::Display for Bot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "pos=<{},{},{}>, r={}", self.x, self.y, self.z, self.r)
+    }
+}
+
+/// `size` nanobots in a cube growing with `size`.
+fn generate_bots(rng: &mut Rng, size: usize) -> Vec<Bot> {
+    let extent = 10 * size as i64 + 10;
+    (0..size.max(1))
+        .map(|_| Bot {
+            x: rng.range(-extent, extent) as i32,
+            y: rng.range(-extent, extent) as i32,
+            z: rng.range(-extent, extent) as i32,
+            r: rng.range(1, extent) as i32,
+        })
+        .collect()
+}
+
+fn generate(rng: &mut Rng, size: usize) -> String {
+    generate_bots(rng, size)
+        .iter()
+        .map(|bot| bot.to_string())
+        .join("\n")
+}
+
 struct InputWalker<'r, 't> {
     caps: CaptureMatches<'r, 't>,
 }
@@ -163,6 +191,13 @@ pos=<1,3,1>, r=1";
         );
     }
 
+    #[test]
+    fn test_generate_round_trip() {
+        let bots = generate_bots(&mut Rng::new(23), 50);
+        let text = bots.iter().map(|bot| bot.to_string()).join("\n");
+        assert_eq!(InputWalker::new(&text).collect::<Vec<_>>(), bots);
+    }
+
     #[test]
     fn test_part1() -> Result<()> {
         Ok(assert_eq!(part1(INPUT)?, 7))
@@ -1,11 +1,13 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::{CaptureMatches, Captures, Regex};
 use std::cmp::min;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
 }
 
 #[derive(Debug, Clone)]
@@ -199,6 +201,86 @@ impl Unit {
     }
 }
 
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} units each with {} hit points ",
+            self.units, self.hit_points
+        )?;
+        let clauses: Vec<_> = [("weak", &self.weak), ("immune", &self.immune)]
+            .iter()
+            .filter(|(_, types)| !types.is_empty())
+            .map(|(key, types)| format!("{} to {}", key, types.join(", ")))
+            .collect();
+        if !clauses.is_empty() {
+            write!(f, "({}) ", clauses.join("; "))?;
+        }
+        write!(
+            f,
+            "with an attack that does {} {} damage at initiative {}",
+            self.damage, self.damage_type, self.initiative
+        )
+    }
+}
+
+const DAMAGE_TYPES: [&str; 5] = ["bludgeoning", "cold", "fire", "radiation", "slashing"];
+
+/// `size` groups per army, with distinct initiatives across both armies.
+fn generate_units(rng: &mut Rng, size: usize) -> Vec<Unit> {
+    let size = size.max(1);
+    let mut initiatives: Vec<u32> = (1..=2 * size as u32).collect();
+    rng.shuffle(&mut initiatives);
+    initiatives
+        .into_iter()
+        .enumerate()
+        .map(|(i, initiative)| {
+            let mut types = DAMAGE_TYPES.to_vec();
+            rng.shuffle(&mut types);
+            let weak_count = rng.below(3);
+            let immune_count = rng.below(3);
+            let hit_points = rng.range(1, 12000) as u32;
+            Unit {
+                id: i % size + 1,
+                units: rng.range(1, 10000) as u32,
+                hit_points,
+                weak: types[..weak_count].iter().map(|&t| t.into()).collect(),
+                immune: types[weak_count..weak_count + immune_count]
+                    .iter()
+                    .map(|&t| t.into())
+                    .collect(),
+                damage: rng.range(1, hit_points as i64 / 2 + 1) as u32,
+                damage_type: rng.choose(&DAMAGE_TYPES).to_string(),
+                initiative,
+                unit_type: if i < size {
+                    UnitType::ImmuneSystem
+                } else {
+                    UnitType::Infection
+                },
+            }
+        })
+        .collect()
+}
+
+fn format_armies(units: &[Unit]) -> String {
+    let army = |unit_type| {
+        units
+            .iter()
+            .filter(|u| u.unit_type == unit_type)
+            .map(|u| u.to_string())
+            .join("\n")
+    };
+    format!(
+        "Immune System:\n{}\n\nInfection:\n{}",
+        army(UnitType::ImmuneSystem),
+        army(UnitType::Infection)
+    )
+}
+
+fn generate(rng: &mut Rng, size: usize) -> String {
+    format_armies(&generate_units(rng, size))
+}
+
 struct UnitWalker<'r, 't> {
     caps: CaptureMatches<'r, 't>,
     unit_type: UnitType,
@@ -310,6 +392,16 @@ Infection:
         Ok(assert_eq!(part1(INPUT)?, 5216))
     }
 
+    #[test]
+    fn test_generate_round_trip() {
+        let units = generate_units(&mut Rng::new(24), 10);
+        let reindeer = Reindeer::new(&format_armies(&units), 0);
+        assert_eq!(reindeer.units.len(), units.len());
+        for unit in units {
+            assert_eq!(reindeer.units[&unit.key()].to_string(), unit.to_string());
+        }
+    }
+
     #[test]
     fn test_boost() {
         let mut reindeer = Reindeer::new(INPUT, 1570);
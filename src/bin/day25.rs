@@ -1,8 +1,9 @@
-use aoc2018::{dispatch, Result};
+use aoc2018::{Day, Result, Rng};
+use itertools::Itertools;
 use std::collections::HashMap;
 
 fn main() {
-    dispatch(&part1, &part2)
+    Day::new(&part1, &part2).generator(&generate).dispatch()
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
@@ -22,6 +23,13 @@ impl Point {
     }
 }
 
+/// `size` points in the same small 4D box as the puzzle's.
+fn generate(rng: &mut Rng, size: usize) -> String {
+    (0..size.max(1))
+        .map(|_| (0..4).map(|_| rng.range(-8, 8)).join(","))
+        .join("\n")
+}
+
 fn part1(input: &str) -> Result<usize> {
     let points: Vec<_> = input
         .split('\n')
@@ -77,6 +85,14 @@ mod tests {
 9,0,0,0
 12,0,0,0";
 
+    #[test]
+    fn test_generate() -> Result<()> {
+        let input = generate(&mut Rng::new(25), 100);
+        assert_eq!(input.lines().count(), 100);
+        assert!(part1(&input)? >= 1);
+        Ok(())
+    }
+
     #[test]
     fn test_part1() -> Result<()> {
         Ok(assert_eq!(part1(INPUT)?, 2))
@@ -0,0 +1,217 @@
+use crate::rng::Rng;
+use crate::shared::{GenFunc, Result};
+use std::fmt::{self, Display};
+use std::ops::RangeInclusive;
+use std::panic::{self, AssertUnwindSafe};
+
+type Solver<'a, T> = &'a dyn Fn(&str) -> Result<T>;
+
+/// Checks a day's fast path against a straightforward reference on generated inputs.
+///
+/// Inputs are tried smallest `size` first, so the first divergence found is already
+/// small; `shrink_lines` additionally deletes input lines while the answers still differ.
+pub struct DiffTest<'a, T> {
+    generator: &'a GenFunc,
+    fast: Solver<'a, T>,
+    reference: Solver<'a, T>,
+    sizes: RangeInclusive<usize>,
+    seeds: u64,
+    shrink_lines: bool,
+}
+
+/// The first generated input on which the two implementations disagree.
+#[derive(Debug)]
+pub struct Divergence {
+    pub seed: u64,
+    pub size: usize,
+    pub input: String,
+    pub fast: Outcome,
+    pub reference: Outcome,
+}
+
+/// A solver's answer, or the error or panic it produced instead.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Answer(String),
+    Failed(String),
+}
+
+impl Outcome {
+    fn agrees_with(&self, other: &Outcome) -> bool {
+        match (self, other) {
+            (Outcome::Answer(a), Outcome::Answer(b)) => a == b,
+            // both rejecting the input is agreement, whatever the messages say
+            (Outcome::Failed(_), Outcome::Failed(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Answer(answer) => write!(f, "{}", answer),
+            Outcome::Failed(err) => write!(f, "failed: {}", err),
+        }
+    }
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "fast and reference disagree on --seed {} --size {}",
+            self.seed, self.size
+        )?;
+        writeln!(f, "fast:      {}", self.fast)?;
+        writeln!(f, "reference: {}", self.reference)?;
+        write!(f, "minimised input:\n{}", self.input)
+    }
+}
+
+fn outcome<T: Display>(solver: Solver<T>, input: &str) -> Outcome {
+    match panic::catch_unwind(AssertUnwindSafe(|| solver(input))) {
+        Ok(Ok(answer)) => Outcome::Answer(answer.to_string()),
+        Ok(Err(err)) => Outcome::Failed(err.to_string()),
+        Err(_) => Outcome::Failed("panicked".into()),
+    }
+}
+
+impl<'a, T: Display> DiffTest<'a, T> {
+    pub fn new(generator: &'a GenFunc, fast: Solver<'a, T>, reference: Solver<'a, T>) -> Self {
+        DiffTest {
+            generator,
+            fast,
+            reference,
+            sizes: 0..=10,
+            seeds: 20,
+            shrink_lines: false,
+        }
+    }
+
+    pub fn sizes(mut self, sizes: RangeInclusive<usize>) -> Self {
+        self.sizes = sizes;
+        self
+    }
+
+    pub fn seeds(mut self, seeds: u64) -> Self {
+        self.seeds = seeds;
+        self
+    }
+
+    /// Only for inputs where any subset of lines still terminates, so not for programs.
+    pub fn shrink_lines(mut self) -> Self {
+        self.shrink_lines = true;
+        self
+    }
+
+    fn compare(&self, input: &str) -> Option<(Outcome, Outcome)> {
+        let fast = outcome(self.fast, input);
+        let reference = outcome(self.reference, input);
+        if fast.agrees_with(&reference) {
+            None
+        } else {
+            Some((fast, reference))
+        }
+    }
+
+    /// Greedily deletes ever smaller runs of lines while the answers still differ.
+    fn shrink(&self, input: &str) -> String {
+        let mut lines: Vec<&str> = input.split('\n').collect();
+        let mut chunk = lines.len() / 2;
+        while chunk > 0 {
+            let mut start = 0;
+            while start + chunk <= lines.len() {
+                let mut candidate = lines.clone();
+                candidate.drain(start..start + chunk);
+                if self.compare(&candidate.join("\n")).is_some() {
+                    lines = candidate;
+                } else {
+                    start += chunk;
+                }
+            }
+            chunk /= 2;
+        }
+        lines.join("\n")
+    }
+
+    pub fn run(&self) -> Option<Divergence> {
+        for size in self.sizes.clone() {
+            for seed in 0..self.seeds {
+                let input = (self.generator)(&mut Rng::new(seed), size);
+                if self.compare(&input).is_none() {
+                    continue;
+                }
+                let input = if self.shrink_lines {
+                    self.shrink(&input)
+                } else {
+                    input
+                };
+                let (fast, reference) = self.compare(&input)?;
+                return Some(Divergence {
+                    seed,
+                    size,
+                    input,
+                    fast,
+                    reference,
+                });
+            }
+        }
+        None
+    }
+
+    /// Panics with the divergence report, for use in tests.
+    pub fn assert_agree(&self) {
+        if let Some(divergence) = self.run() {
+            panic!("{}", divergence);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use failure::bail;
+
+    fn generate(rng: &mut Rng, size: usize) -> String {
+        (0..size)
+            .map(|_| rng.range(0, 9).to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn sum(input: &str) -> Result<u32> {
+        let mut total = 0;
+        for line in input.lines() {
+            total += line.parse::<u32>()?;
+        }
+        Ok(total)
+    }
+
+    fn sum_ignoring_sevens(input: &str) -> Result<u32> {
+        Ok(sum(input)? - 7 * input.lines().filter(|&l| l == "7").count() as u32)
+    }
+
+    fn reject_all(_input: &str) -> Result<u32> {
+        bail!("no")
+    }
+
+    #[test]
+    fn test_agree() {
+        DiffTest::new(&generate, &sum, &sum).assert_agree();
+        DiffTest::new(&generate, &reject_all, &reject_all).assert_agree();
+    }
+
+    #[test]
+    fn test_minimised_divergence() {
+        let divergence = DiffTest::new(&generate, &sum_ignoring_sevens, &sum)
+            .sizes(5..=20)
+            .shrink_lines()
+            .run()
+            .expect("divergence");
+        assert_eq!(divergence.size, 5);
+        assert_eq!(divergence.input, "7");
+        assert_eq!(divergence.fast, Outcome::Answer("0".into()));
+        assert_eq!(divergence.reference, Outcome::Answer("7".into()));
+    }
+}
@@ -1,7 +1,12 @@
 // mod direction;
 // mod parsers;
 // mod position;
+pub mod difftest;
+pub mod memo;
+pub mod rng;
 pub mod shared;
 // mod tablet;
 //
-pub use crate::shared::{dispatch, Result};
+pub use crate::memo::Memo;
+pub use crate::rng::Rng;
+pub use crate::shared::{dispatch, Day, Params, Result, Whitespace};
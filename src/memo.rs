@@ -0,0 +1,175 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// How well a `Memo` did, printed alongside the elapsed time under `--time`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+thread_local! {
+    static FINISHED: RefCell<Vec<(&'static str, Stats)>> = const { RefCell::new(vec![]) };
+}
+
+/// Stats of the memos dropped on this thread since the last call.
+pub fn take_stats() -> Vec<(&'static str, Stats)> {
+    FINISHED.with(|finished| finished.borrow_mut().drain(..).collect())
+}
+
+/// A cache owned by one solver run, so nothing leaks between inputs.
+///
+/// With a bound, the oldest entries are evicted first.
+pub struct Memo<K, V> {
+    name: &'static str,
+    values: HashMap<K, V>,
+    order: VecDeque<K>,
+    bound: Option<usize>,
+    stats: Stats,
+}
+
+impl<K, V> Memo<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    pub fn new(name: &'static str) -> Self {
+        Memo {
+            name,
+            values: HashMap::new(),
+            order: VecDeque::new(),
+            bound: None,
+            stats: Stats::default(),
+        }
+    }
+
+    pub fn bounded(name: &'static str, bound: usize) -> Self {
+        assert!(bound > 0, "a memo needs room for at least one entry");
+        let mut memo = Memo::new(name);
+        memo.bound = Some(bound);
+        memo
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        let value = self.values.get(key).cloned();
+        if value.is_some() {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+        }
+        value
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> V {
+        if let Some(bound) = self.bound {
+            if !self.values.contains_key(&key) {
+                while self.values.len() >= bound {
+                    let oldest = self.order.pop_front().expect("memo order out of sync");
+                    self.values.remove(&oldest);
+                    self.stats.evictions += 1;
+                }
+                self.order.push_back(key.clone());
+            }
+        }
+        self.values.insert(key, value.clone());
+        value
+    }
+
+    /// For non-recursive functions; recursive ones use `get` and `insert` around the call.
+    pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce(&K) -> V) -> V {
+        match self.get(&key) {
+            Some(value) => value,
+            None => {
+                let value = f(&key);
+                self.insert(key, value)
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.order.clear();
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+}
+
+impl<K, V> Drop for Memo<K, V> {
+    fn drop(&mut self) {
+        let stats = (self.name, self.stats);
+        FINISHED.with(|finished| finished.borrow_mut().push(stats));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_or_insert_with() {
+        let mut memo = Memo::new("squares");
+        let mut calls = 0;
+        for &n in &[3, 4, 3, 3] {
+            let square = memo.get_or_insert_with(n, |n| {
+                calls += 1;
+                n * n
+            });
+            assert_eq!(square, n * n);
+        }
+        assert_eq!(calls, 2);
+        assert_eq!(
+            memo.stats(),
+            Stats {
+                hits: 2,
+                misses: 2,
+                evictions: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_bounded() {
+        let mut memo = Memo::bounded("bounded", 2);
+        memo.insert(1, 'a');
+        memo.insert(2, 'b');
+        memo.insert(2, 'c');
+        memo.insert(3, 'd');
+        assert_eq!(memo.len(), 2);
+        assert_eq!(memo.get(&1), None);
+        assert_eq!(memo.get(&2), Some('c'));
+        assert_eq!(memo.stats().evictions, 1);
+        memo.clear();
+        assert!(memo.is_empty());
+    }
+
+    #[test]
+    fn test_take_stats() {
+        {
+            let mut memo = Memo::new("dropped");
+            memo.get_or_insert_with(1, |&n| n);
+        }
+        assert_eq!(
+            take_stats(),
+            vec![(
+                "dropped",
+                Stats {
+                    hits: 0,
+                    misses: 1,
+                    evictions: 0
+                }
+            )]
+        );
+    }
+}
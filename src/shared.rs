@@ -1,9 +1,16 @@
+use crate::memo;
+use crate::rng::Rng;
 use clap::{App, Arg};
-use failure::{bail, Error};
+use failure::{bail, err_msg, format_err, Error};
+use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::fs::File;
-use std::io::{self, Read};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
 use std::result;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 pub type Result<T> = result::Result<T, Error>;
 
@@ -17,9 +24,103 @@ enum Source {
     File(String),
 }
 
+enum Command {
+    Solve(Part, Source),
+    Mode(String, Source, Params),
+    Serve,
+    Generate { seed: u64, size: usize },
+}
+
 struct Args {
-    part: Part,
-    source: Source,
+    command: Command,
+    time: bool,
+}
+
+/// How much whitespace a day's solvers get to see.
+///
+/// Either way a BOM is stripped, CRLF line endings become `\n` and the final newline is
+/// dropped, so days can split on `'\n'` and parse the last line as-is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Whitespace {
+    /// Also drops trailing spaces on every line and trailing blank lines.
+    Trim,
+    /// Keeps spaces where they are, for inputs where they mean something.
+    Keep,
+}
+
+fn normalise(input: &str, whitespace: Whitespace) -> String {
+    let lines = input
+        .trim_start_matches('\u{feff}')
+        .split('\n')
+        .map(|line| line.trim_end_matches('\r'));
+    let text = match whitespace {
+        Whitespace::Trim => lines.map(str::trim_end).collect::<Vec<_>>().join("\n"),
+        Whitespace::Keep => lines.collect::<Vec<_>>().join("\n"),
+    };
+    text.trim_end_matches('\n').to_string()
+}
+
+/// The `-o key=value` options passed to a mode.
+#[derive(Debug, Default)]
+pub struct Params {
+    values: HashMap<String, String>,
+}
+
+impl Params {
+    fn parse<'a>(options: impl Iterator<Item = &'a str>) -> Result<Self> {
+        let mut values = HashMap::new();
+        for option in options {
+            let mut parts = option.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => values.insert(key.into(), value.into()),
+                _ => bail!("option `{}` is not of the form key=value", option),
+            };
+        }
+        Ok(Params { values })
+    }
+
+    /// Sets an option, as `-o key=value` would.
+    pub fn with(mut self, key: &str, value: &str) -> Self {
+        self.values.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|value| value.as_str())
+    }
+
+    pub fn get<T>(&self, key: &str, default: T) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.get_str(key) {
+            Some(value) => value
+                .parse()
+                .map_err(|err| format_err!("option `{}`: {}", key, err)),
+            None => Ok(default),
+        }
+    }
+
+    /// A comma-separated list, e.g. `-o thresholds=10,-5`.
+    pub fn get_list<T>(&self, key: &str, default: Vec<T>) -> Result<Vec<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.get_str(key) {
+            Some(values) => values
+                .split(',')
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|err| format_err!("option `{}`: {}", key, err))
+                })
+                .collect(),
+            None => Ok(default),
+        }
+    }
 }
 
 fn read_stdin() -> Result<String> {
@@ -45,19 +146,73 @@ fn parse_input() -> Result<Args> {
                 .default_value("1")
                 .possible_values(&["1", "2"]),
         )
+        .arg(
+            Arg::with_name("time")
+                .long("time")
+                .help("Prints the time taken by the solver, and its cache stats, to stderr"),
+        )
+        .arg(
+            Arg::with_name("serve")
+                .long("serve")
+                .help("Answers JSON-lines requests from stdin until it is closed"),
+        )
+        .arg(
+            Arg::with_name("gen")
+                .long("gen")
+                .help("Prints a random puzzle input instead of solving one"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("size")
+                .long("size")
+                .takes_value(true)
+                .default_value("10"),
+        )
+        .arg(
+            Arg::with_name("mode")
+                .short("m")
+                .long("mode")
+                .takes_value(true)
+                .help("Runs one of the day's extra modes instead of a part"),
+        )
+        .arg(
+            Arg::with_name("option")
+                .short("o")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("key=value")
+                .help("Sets an option for the mode"),
+        )
         .arg(
             Arg::with_name("input")
                 .help("Sets the input file to use, or `-` for stdin")
-                .required(true)
+                .required_unless_one(&["serve", "gen"])
                 .index(1),
         )
         .get_matches();
 
-    let part = match matches.value_of("part").unwrap_or("1") {
-        "1" => Part::Part1,
-        "2" => Part::Part2,
-        _ => bail!("Invalid part"),
-    };
+    let time = matches.is_present("time");
+    if matches.is_present("serve") {
+        return Ok(Args {
+            command: Command::Serve,
+            time,
+        });
+    }
+    if matches.is_present("gen") {
+        let seed = matches.value_of("seed").unwrap_or("0").parse()?;
+        let size = matches.value_of("size").unwrap_or("10").parse()?;
+        return Ok(Args {
+            command: Command::Generate { seed, size },
+            time,
+        });
+    }
+
     let source = match matches
         .value_of("input")
         .expect("input is required but missing")
@@ -65,24 +220,218 @@ fn parse_input() -> Result<Args> {
         "-" => Source::Stdin,
         filename => Source::File(filename.into()),
     };
-    Ok(Args { part, source })
+    if let Some(mode) = matches.value_of("mode") {
+        let params = Params::parse(matches.values_of("option").into_iter().flatten())?;
+        return Ok(Args {
+            command: Command::Mode(mode.into(), source, params),
+            time,
+        });
+    }
+
+    let part = match matches.value_of("part").unwrap_or("1") {
+        "1" => Part::Part1,
+        "2" => Part::Part2,
+        _ => bail!("Invalid part"),
+    };
+    Ok(Args {
+        command: Command::Solve(part, source),
+        time,
+    })
 }
 
-type DayFunc<T> = Fn(&str) -> Result<T>;
+type DayFunc<T> = dyn Fn(&str) -> Result<T>;
+
+/// An extra way of running a day, reading the raw input as it likes.
+pub type ModeFunc = dyn Fn(&mut dyn BufRead, &Params) -> Result<String>;
 
-fn run<S, T>(part1: &DayFunc<S>, part2: &DayFunc<T>) -> Result<String>
+/// Produces a random puzzle input of roughly `size` records (see each day's `generate`).
+pub type GenFunc = dyn Fn(&mut Rng, usize) -> String;
+
+fn solve<S, T>(part1: &DayFunc<S>, part2: &DayFunc<T>, part: &Part, input: &str) -> Result<String>
 where
     S: Display,
     T: Display,
 {
-    let args = parse_input()?;
-    let input = match args.source {
-        Source::Stdin => read_stdin(),
-        Source::File(filename) => read_file(&filename),
-    }?;
-    match args.part {
-        Part::Part1 => part1(&input).map(|res| format!("{}", res)),
-        Part::Part2 => part2(&input).map(|res| format!("{}", res)),
+    match part {
+        Part::Part1 => part1(input).map(|res| format!("{}", res)),
+        Part::Part2 => part2(input).map(|res| format!("{}", res)),
+    }
+}
+
+fn timed<R>(f: impl FnOnce() -> R) -> (R, Duration) {
+    let start = Instant::now();
+    let res = f();
+    (res, start.elapsed())
+}
+
+/// A day's solvers plus the optional extras the command line can reach.
+pub struct Day<'a, S, T> {
+    part1: &'a DayFunc<S>,
+    part2: &'a DayFunc<T>,
+    generator: Option<&'a GenFunc>,
+    modes: Vec<(&'a str, &'a ModeFunc)>,
+    whitespace: Whitespace,
+}
+
+impl<'a, S, T> Day<'a, S, T>
+where
+    S: Display,
+    T: Display,
+{
+    pub fn new(part1: &'a DayFunc<S>, part2: &'a DayFunc<T>) -> Self {
+        Day {
+            part1,
+            part2,
+            generator: None,
+            modes: vec![],
+            whitespace: Whitespace::Trim,
+        }
+    }
+
+    pub fn generator(mut self, generator: &'a GenFunc) -> Self {
+        self.generator = Some(generator);
+        self
+    }
+
+    /// Adds a mode, run with `--mode <name>` and given the input unread and unnormalised.
+    pub fn mode(mut self, name: &'a str, mode: &'a ModeFunc) -> Self {
+        self.modes.push((name, mode));
+        self
+    }
+
+    fn run_mode(&self, name: &str, source: Source, params: &Params) -> Result<String> {
+        let mode = match self.modes.iter().find(|(mode, _)| *mode == name) {
+            Some((_, mode)) => mode,
+            None => {
+                let names: Vec<_> = self.modes.iter().map(|(mode, _)| *mode).collect();
+                bail!(
+                    "Unknown mode `{}` (this day has: {})",
+                    name,
+                    names.join(", ")
+                )
+            }
+        };
+        match source {
+            Source::Stdin => {
+                let stdin = io::stdin();
+                let mut handle = stdin.lock();
+                mode(&mut handle, params)
+            }
+            Source::File(filename) => mode(&mut BufReader::new(File::open(filename)?), params),
+        }
+    }
+
+    pub fn whitespace(mut self, whitespace: Whitespace) -> Self {
+        self.whitespace = whitespace;
+        self
+    }
+
+    fn solve(&self, part: &Part, input: &str) -> Result<String> {
+        solve(
+            self.part1,
+            self.part2,
+            part,
+            &normalise(input, self.whitespace),
+        )
+    }
+
+    /// Answers a single serve request of the form `{"part": 2, "input": "…"}`.
+    ///
+    /// `id`, `day` and `part` are echoed back so callers can match up responses. Panics in
+    /// the solver are reported as errors rather than taking the process down with them.
+    fn respond(&self, line: &str) -> Value {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(err) => return json!({ "error": format!("invalid request: {}", err) }),
+        };
+        let mut response = json!({});
+        for key in &["id", "day", "part"] {
+            if let Some(value) = request.get(key) {
+                response[key] = value.clone();
+            }
+        }
+
+        let answer = || -> Result<(String, Duration)> {
+            let part = match request["part"].as_u64() {
+                Some(1) => Part::Part1,
+                Some(2) => Part::Part2,
+                _ => bail!("`part` must be 1 or 2"),
+            };
+            let input = request["input"]
+                .as_str()
+                .ok_or_else(|| err_msg("`input` must be a string"))?;
+            let (res, elapsed) =
+                timed(|| panic::catch_unwind(AssertUnwindSafe(|| self.solve(&part, input))));
+            // nobody asked for the stats; don't let them pile up between requests
+            memo::take_stats();
+            let answer = res.map_err(|_| err_msg("solver panicked"))??;
+            Ok((answer, elapsed))
+        };
+        match answer() {
+            Ok((answer, elapsed)) => {
+                response["answer"] = json!(answer);
+                response["elapsed_us"] = json!(elapsed.as_micros() as u64);
+            }
+            Err(err) => response["error"] = json!(err.to_string()),
+        }
+        response
+    }
+
+    fn serve(&self) -> Result<()> {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        for line in stdin.lock().lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = self.respond(&line);
+            let mut handle = stdout.lock();
+            writeln!(handle, "{}", response)?;
+            handle.flush()?;
+        }
+        Ok(())
+    }
+
+    fn run(&self) -> Result<Option<String>> {
+        let args = parse_input()?;
+        let (res, elapsed) = match args.command {
+            Command::Serve => return self.serve().map(|_| None),
+            Command::Generate { seed, size } => {
+                let generator = self
+                    .generator
+                    .ok_or_else(|| err_msg("No input generator for this day"))?;
+                // no trailing newline, so the output can be piped straight back in
+                print!("{}", generator(&mut Rng::new(seed), size));
+                return Ok(None);
+            }
+            Command::Mode(name, source, params) => timed(|| self.run_mode(&name, source, &params)),
+            Command::Solve(part, source) => {
+                let input = match source {
+                    Source::Stdin => read_stdin(),
+                    Source::File(filename) => read_file(&filename),
+                }?;
+                timed(|| self.solve(&part, &input))
+            }
+        };
+        if args.time {
+            eprintln!("elapsed: {:?}", elapsed);
+            for (name, stats) in memo::take_stats() {
+                eprintln!(
+                    "{}: {} hits, {} misses, {} evictions",
+                    name, stats.hits, stats.misses, stats.evictions
+                );
+            }
+        }
+        res.map(Some)
+    }
+
+    pub fn dispatch(self) {
+        match self.run() {
+            Ok(Some(result)) => println!("{}", result),
+            Ok(None) => {}
+            Err(err) => println!("{}", err),
+        };
     }
 }
 
@@ -91,8 +440,66 @@ where
     S: Display,
     T: Display,
 {
-    match run(part1, part2) {
-        Ok(result) => println!("{}", result),
-        Err(err) => println!("{}", err),
-    };
+    Day::new(part1, part2).dispatch()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part1(input: &str) -> Result<usize> {
+        Ok(input.len())
+    }
+
+    fn part2(input: &str) -> Result<usize> {
+        Ok(input.parse()?)
+    }
+
+    #[test]
+    fn test_normalise() {
+        let input = "\u{feff}  ab \r\ncd\t\r\n\r\n\n";
+        assert_eq!(normalise(input, Whitespace::Trim), "  ab\ncd");
+        assert_eq!(normalise(input, Whitespace::Keep), "  ab \ncd\t");
+        assert_eq!(normalise("509671\n", Whitespace::Trim), "509671");
+        assert_eq!(normalise("", Whitespace::Trim), "");
+    }
+
+    #[test]
+    fn test_params() -> Result<()> {
+        let params = Params::parse(vec!["width=3", "name=a=b"].into_iter())?;
+        assert_eq!(params.get("width", 1)?, 3);
+        assert_eq!(params.get("height", 1)?, 1);
+        assert_eq!(params.get_str("name"), Some("a=b"));
+        assert!(params.get::<u32>("name", 0).is_err());
+        let params = Params::parse(vec!["sizes=2, 3,4"].into_iter())?;
+        assert_eq!(params.get_list::<u32>("sizes", vec![])?, vec![2, 3, 4]);
+        assert_eq!(params.get_list("other", vec![1])?, vec![1]);
+        assert!(Params::parse(vec!["width"].into_iter()).is_err());
+        assert_eq!(Params::default().with("width", "5").get("width", 1)?, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_respond() {
+        let response =
+            Day::new(&part1, &part2).respond(r#"{"id": 7, "day": 1, "part": 1, "input": "abc\n"}"#);
+        assert_eq!(response["answer"], "3");
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["day"], 1);
+        assert!(response["elapsed_us"].is_u64());
+    }
+
+    #[test]
+    fn test_respond_errors() {
+        let day = Day::new(&part1, &part2);
+        let response = day.respond(r#"{"part": 2, "input": "abc"}"#);
+        assert_eq!(response["error"], "invalid digit found in string");
+        let response = day.respond(r#"{"part": 3, "input": ""}"#);
+        assert_eq!(response["error"], "`part` must be 1 or 2");
+        let response = day.respond("not json");
+        assert!(response["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid request"));
+    }
 }